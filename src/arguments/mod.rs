@@ -13,6 +13,20 @@ pub struct Opt {
     #[structopt(short, long)]
     pub speciesism: bool,
 
+    /// Use novelty search, creatures are selected by how different they move
+    #[structopt(long, conflicts_with = "speciesism")]
+    pub novelty: bool,
+
+    /// Set number of simulation steps run per update in headless mode
+    /// with default value of 0, which runs a whole evaluation per update
+    #[structopt(long, default_value = "0")]
+    pub steps_per_update: usize,
+
+    /// Set number of threads creatures are evaluated on in headless mode
+    /// with default value of 1, which simulates them in the app's world
+    #[structopt(long, default_value = "1")]
+    pub threads: usize,
+
     /// Set number of max generations
     #[structopt(long, default_value = "100")]
     pub max_generations: usize,
@@ -31,6 +45,20 @@ pub struct Opt {
     #[structopt(long = "crossover", default_value = "0.5")]
     pub crossover_chance: f32,
 
+    /// Set how parents are chosen: truncation, tournament, rank or roulette
+    /// with default value of truncation, where neighbours in fitness order breed together
+    #[structopt(
+        long,
+        default_value = "truncation",
+        possible_values = &["truncation", "tournament", "rank", "roulette"]
+    )]
+    pub selection: String,
+
+    /// Set number of individuals competing in every tournament
+    /// with default value of 3, only used with tournament selection
+    #[structopt(long, default_value = "3")]
+    pub tournament_size: usize,
+
     /// Set number of population
     /// with default value of 100
     #[structopt(long, default_value = "100")]
@@ -40,6 +68,11 @@ pub struct Opt {
     #[structopt(long)]
     pub playground: bool,
 
+    /// Set file the sensor readings of every step are written to
+    /// when the playground runs in headless mode
+    #[structopt(long)]
+    pub record_sensors: Option<String>,
+
     /// Set if testing algorithm
     /// with default value of false
     #[structopt(long = "test")]
@@ -51,6 +84,11 @@ pub struct Opt {
     #[structopt(long = "test-count", default_value = "10")]
     pub test_count: usize,
 
+    /// Set seed for every random decision of the run
+    /// with a random seed by default, it is printed so the run can be replayed
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
     /// Instance to run the algorithm on
     /// Instances are defined in config.ron
     #[structopt(long, default_value = "0")]
@@ -1,14 +1,199 @@
-use bevy::log::info;
+use bevy::{
+    log::{info, warn},
+    math::Vec2,
+};
 use once_cell::sync::Lazy;
 use ron::de::from_reader;
 use serde::Deserialize;
 
 use std::fs::File;
 
+/// Which nodes collide with each other, besides the ground
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum NodeCollisions {
+    /// Nodes go through each other
+    Disabled,
+    /// Nodes of the same creature collide, except the ones joined by a muscle
+    SameCreature,
+    /// Also nodes of different creatures collide, they all start at the same place.
+    /// Only possible when creatures share a world, so it evaluates on a single thread
+    AllCreatures,
+}
+
+impl Default for NodeCollisions {
+    fn default() -> Self {
+        NodeCollisions::Disabled
+    }
+}
+
+/// Shape of the ground, heights are given as a function of x
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum TerrainProfile {
+    Flat,
+    /// Straight line through the origin
+    Slope {
+        slope: f32,
+    },
+    /// Sine wave through the origin
+    SineHills {
+        amplitude: f32,
+        wavelength: f32,
+    },
+    /// Stairs going up to the right, `width` long and `height` tall
+    Steps {
+        height: f32,
+        width: f32,
+    },
+    /// Heights read from a RON file with a list of numbers,
+    /// sampled every `spacing` starting at `start`
+    Heightmap {
+        path: String,
+        start: f32,
+        spacing: f32,
+    },
+}
+
+impl Default for TerrainProfile {
+    fn default() -> Self {
+        TerrainProfile::Flat
+    }
+}
+
+impl TerrainProfile {
+    /// Lengths heights are divided by have to be positive
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            TerrainProfile::SineHills { wavelength, .. } if *wavelength <= 0.0 => Err(format!(
+                "sine hills wavelength has to be positive, it is {}",
+                wavelength
+            )),
+            TerrainProfile::Steps { width, .. } if *width <= 0.0 => {
+                Err(format!("steps width has to be positive, it is {}", width))
+            }
+            TerrainProfile::Heightmap { spacing, .. } if *spacing <= 0.0 => Err(format!(
+                "heightmap spacing has to be positive, it is {}",
+                spacing
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Fluid creatures move through
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Medium {
+    /// Only the air friction of the instance slows muscles down
+    Air,
+    /// Muscles and bones are dragged, moving across themselves is harder
+    /// than moving along themselves, like a fin
+    Water {
+        /// Upward force on every node, relative to the weight of a node of mass 1.
+        /// Lighter nodes float, heavier ones sink
+        buoyancy: f32,
+        /// Drag of a segment moving across itself, per unit of length
+        normal_drag: f32,
+        /// Drag of a segment moving along itself, per unit of length
+        tangential_drag: f32,
+    },
+}
+
+impl Default for Medium {
+    fn default() -> Self {
+        Medium::Air
+    }
+}
+
+/// Static obstacle nodes collide with
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Obstacle {
+    /// Solid axis aligned box between two corners
+    Box { min: Vec2, max: Vec2 },
+    /// Hole dug into the terrain from `start`, `width` long and `depth` deep
+    Pit { start: f32, width: f32, depth: f32 },
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Instance {
+    /// Downward acceleration of the nodes. It used to be multiplied by
+    /// `fixed_time_step`, so older configs need it multiplied by their time step
     pub gravity: f32,
     pub air_friction: f32,
+    #[serde(default)]
+    pub node_collisions: NodeCollisions,
+    #[serde(default)]
+    pub terrain: TerrainProfile,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default)]
+    pub medium: Medium,
+}
+
+/// Force law muscles pull/push their nodes with
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum MuscleModel {
+    /// Force grows with the square of the relative length error, without damping
+    Legacy,
+    /// Spring towards the target length, damped by the muscle's `damping`
+    SpringDamper,
+}
+
+impl Default for MuscleModel {
+    fn default() -> Self {
+        MuscleModel::SpringDamper
+    }
+}
+
+/// How muscles choose the length they move towards
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum MuscleActuation {
+    /// Contracted for `contracted_time` of the internal clock, extended the rest
+    DutyCycle,
+    /// Moves continuously between both lengths following the muscle's
+    /// waveform, shifted by its phase
+    Oscillator,
+    /// The creature's neural network controller chooses every length
+    /// from the internal clock, the clock of the muscle and the creature's sensors
+    NeuralNetwork,
+    /// Like `NeuralNetwork`, with a network whose topology evolves.
+    /// It is run for every muscle with the clock shifted by the muscle's phase
+    Neat,
+}
+
+impl Default for MuscleActuation {
+    fn default() -> Self {
+        MuscleActuation::DutyCycle
+    }
+}
+
+/// What creatures are rewarded for at the end of an evaluation
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum FitnessFunction {
+    /// Distance travelled along the ground from the origin
+    Distance,
+    /// Distance travelled per unit of energy spent by the muscles.
+    /// One unit is always added to the energy, so creatures that barely move
+    /// don't get a huge fitness
+    DistancePerEnergy,
+}
+
+impl Default for FitnessFunction {
+    fn default() -> Self {
+        FitnessFunction::Distance
+    }
+}
+
+/// Numerical method used to advance the nodes every step
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Integrator {
+    SemiImplicitEuler,
+    VelocityVerlet,
+    RungeKutta4,
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Integrator::SemiImplicitEuler
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,22 +203,81 @@ pub struct Config {
     pub time_scale: f32,
     pub fixed_time_step: f32,
     pub node_size: f32,
+    #[serde(default = "default_max_node_speed")]
+    pub max_node_speed: f32,
+    #[serde(default)]
+    pub integrator: Integrator,
+    #[serde(default)]
+    pub muscle_model: MuscleModel,
+    #[serde(default)]
+    pub muscle_actuation: MuscleActuation,
+    #[serde(default)]
+    pub fitness_function: FitnessFunction,
+    /// Whether new muscles can be triggered by ground contact instead of their clock
+    #[serde(default)]
+    pub reflex_muscles: bool,
 
     // Min and max values settings
     pub min_friction: f32,
     pub max_friction: f32,
+    #[serde(default = "default_min_mass")]
+    pub min_mass: f32,
+    #[serde(default = "default_max_mass")]
+    pub max_mass: f32,
     pub min_strength: f32,
     pub max_strength: f32,
+    #[serde(default = "default_min_damping")]
+    pub min_damping: f32,
+    #[serde(default = "default_max_damping")]
+    pub max_damping: f32,
     pub min_contracted_length: f32,
     pub max_contracted_length: f32,
     pub min_extended_length: f32,
     pub max_extended_length: f32,
     pub min_contracted_time: f32,
     pub max_contracted_time: f32,
+    #[serde(default = "default_min_internal_clock_size")]
+    pub min_internal_clock_size: f32,
+    #[serde(default = "default_max_internal_clock_size")]
+    pub max_internal_clock_size: f32,
+    /// Creatures can have up to this many independent clocks
+    #[serde(default = "default_max_clocks")]
+    pub max_clocks: usize,
+    #[serde(default = "default_max_controller_weight")]
+    pub max_controller_weight: f32,
+
+    // Neural network controller settings
+    #[serde(default = "default_controller_hidden_neurons")]
+    pub controller_hidden_neurons: usize,
 
     // Genetic algorithm settings
     pub max_unchanged_generations: usize,
     pub improvement_threshold: f32,
+    /// Initial distance under which creatures belong to the same species
+    #[serde(default = "default_compatibility_threshold")]
+    pub compatibility_threshold: f32,
+    /// The compatibility threshold moves by `compatibility_threshold_step` every
+    /// generation towards having this many species, 0 keeps it fixed
+    #[serde(default = "default_target_species_count")]
+    pub target_species_count: usize,
+    #[serde(default = "default_compatibility_threshold_step")]
+    pub compatibility_threshold_step: f32,
+    /// Weight in the distance of the nodes and connections only one creature has
+    #[serde(default = "default_topology_distance_coefficient")]
+    pub topology_distance_coefficient: f32,
+    /// Weight in the distance of the differences between nodes and muscles both creatures have
+    #[serde(default = "default_parameter_distance_coefficient")]
+    pub parameter_distance_coefficient: f32,
+    /// Closest behaviours the novelty of a creature is measured against
+    #[serde(default = "default_novelty_neighbours")]
+    pub novelty_neighbours: usize,
+    /// Most novel creatures of every generation kept in the novelty archive
+    #[serde(default = "default_novelty_archive_additions")]
+    pub novelty_archive_additions: usize,
+    /// Weight of the fitness in the score of novelty search, from 0 to 1.
+    /// 0 only rewards novelty and 1 only fitness
+    #[serde(default)]
+    pub novelty_fitness_weight: f32,
 
     // Mutation settings
     pub elimination_mutation_chance_modifier: f32,
@@ -44,6 +288,74 @@ pub struct Config {
     pub instances: Vec<Instance>,
 }
 
+fn default_max_node_speed() -> f32 {
+    50.0
+}
+
+fn default_min_mass() -> f32 {
+    0.5
+}
+
+fn default_max_mass() -> f32 {
+    2.0
+}
+
+fn default_min_damping() -> f32 {
+    0.0
+}
+
+fn default_max_damping() -> f32 {
+    2.0
+}
+
+fn default_min_internal_clock_size() -> f32 {
+    0.1
+}
+
+fn default_max_internal_clock_size() -> f32 {
+    0.5
+}
+
+fn default_max_clocks() -> usize {
+    1
+}
+
+fn default_max_controller_weight() -> f32 {
+    5.0
+}
+
+fn default_controller_hidden_neurons() -> usize {
+    4
+}
+
+fn default_compatibility_threshold() -> f32 {
+    0.8
+}
+
+fn default_target_species_count() -> usize {
+    5
+}
+
+fn default_compatibility_threshold_step() -> f32 {
+    0.05
+}
+
+fn default_topology_distance_coefficient() -> f32 {
+    1.0
+}
+
+fn default_parameter_distance_coefficient() -> f32 {
+    1.0
+}
+
+fn default_novelty_neighbours() -> usize {
+    15
+}
+
+fn default_novelty_archive_additions() -> usize {
+    2
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -51,20 +363,44 @@ impl Default for Config {
             fixed_time_step: 0.01,
             evaluation_time: 15.0,
             node_size: 0.08,
+            max_node_speed: default_max_node_speed(),
+            integrator: Integrator::SemiImplicitEuler,
+            muscle_model: MuscleModel::SpringDamper,
+            muscle_actuation: MuscleActuation::DutyCycle,
+            fitness_function: FitnessFunction::Distance,
+            reflex_muscles: false,
 
             min_friction: 0.3,
             max_friction: 1.0,
+            min_mass: default_min_mass(),
+            max_mass: default_max_mass(),
             min_strength: 0.5,
             max_strength: 10.0,
+            min_damping: default_min_damping(),
+            max_damping: default_max_damping(),
             min_contracted_length: 0.08,
             max_contracted_length: 0.40,
             min_extended_length: 0.08,
             max_extended_length: 0.40,
             min_contracted_time: 0.1,
             max_contracted_time: 1.0,
+            min_internal_clock_size: default_min_internal_clock_size(),
+            max_internal_clock_size: default_max_internal_clock_size(),
+            max_clocks: default_max_clocks(),
+            max_controller_weight: default_max_controller_weight(),
+
+            controller_hidden_neurons: default_controller_hidden_neurons(),
 
             max_unchanged_generations: 10,
             improvement_threshold: 0.05,
+            compatibility_threshold: default_compatibility_threshold(),
+            target_species_count: default_target_species_count(),
+            compatibility_threshold_step: default_compatibility_threshold_step(),
+            topology_distance_coefficient: default_topology_distance_coefficient(),
+            parameter_distance_coefficient: default_parameter_distance_coefficient(),
+            novelty_neighbours: default_novelty_neighbours(),
+            novelty_archive_additions: default_novelty_archive_additions(),
+            novelty_fitness_weight: 0.0,
 
             elimination_mutation_chance_modifier: 0.5,
             position_mutation_chance_modifier: 1.0,
@@ -73,30 +409,85 @@ impl Default for Config {
 
             instances: vec![
                 Instance {
-                    gravity: 10.0,
+                    gravity: 0.1,
                     air_friction: 8.0,
+                    node_collisions: NodeCollisions::Disabled,
+                    terrain: TerrainProfile::Flat,
+                    obstacles: Vec::new(),
+                    medium: Medium::Air,
                 },
                 Instance {
-                    gravity: 1.0,
+                    gravity: 0.01,
                     air_friction: 8.0,
+                    node_collisions: NodeCollisions::Disabled,
+                    terrain: TerrainProfile::Flat,
+                    obstacles: Vec::new(),
+                    medium: Medium::Air,
                 },
                 Instance {
-                    gravity: 10.0,
+                    gravity: 0.1,
                     air_friction: 1.0,
+                    node_collisions: NodeCollisions::Disabled,
+                    terrain: TerrainProfile::Flat,
+                    obstacles: Vec::new(),
+                    medium: Medium::Air,
                 },
             ],
         }
     }
 }
 
+impl Config {
+    /// Instances whose gravity looks like it is still multiplied by `fixed_time_step`,
+    /// like the ones of configs written before it stopped being
+    fn old_gravity_instances(&self) -> Vec<usize> {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(|(_, instance)| instance.gravity > MAX_EXPECTED_GRAVITY)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Checks the settings that would break the simulation
+    fn validate(&self) -> Result<(), String> {
+        for (index, instance) in self.instances.iter().enumerate() {
+            instance
+                .terrain
+                .validate()
+                .map_err(|error| format!("Instance {}: {}", index + 1, error))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Old configs used gravities around 10, now that's a hundred times stronger than intended
+const MAX_EXPECTED_GRAVITY: f32 = 1.0;
+
 pub static CONFIG: Lazy<Config> = Lazy::new(load_config);
 
 fn load_config() -> Config {
     match load_config_from_file() {
-        Ok(x) => {
-            info!("Loaded config from file");
-            x
-        }
+        Ok(x) => match x.validate() {
+            Ok(()) => {
+                info!("Loaded config from file");
+                for index in x.old_gravity_instances() {
+                    warn!(
+                        "Instance {}: gravity {} is no longer multiplied by fixed_time_step, \
+                         multiply it by {} if the config predates it.",
+                        index + 1,
+                        x.instances[index].gravity,
+                        x.fixed_time_step
+                    );
+                }
+                x
+            }
+            Err(error) => {
+                warn!("Invalid config. {}. Using default config.", error);
+                Config::default()
+            }
+        },
         Err(err) => {
             info!("Config file error. {}. Using default config.", err.code);
             Config::default()
@@ -110,3 +501,35 @@ fn load_config_from_file() -> Result<Config, ron::error::Error> {
     let config: Config = from_reader(file)?;
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terrains_without_length_are_rejected() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.instances[1].terrain = TerrainProfile::Steps {
+            height: 0.05,
+            width: 0.0,
+        };
+        assert!(config.validate().is_err());
+
+        config.instances[1].terrain = TerrainProfile::SineHills {
+            amplitude: 0.1,
+            wavelength: -1.0,
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_old_gravities_are_noticed() {
+        let mut config = Config::default();
+        assert!(config.old_gravity_instances().is_empty());
+
+        config.instances[2].gravity = 10.0;
+        assert_eq!(config.old_gravity_instances(), vec![2]);
+    }
+}
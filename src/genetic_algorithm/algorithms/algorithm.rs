@@ -1,22 +1,27 @@
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
+use std::cmp::Ordering;
 
-use bevy::log::info;
-use ron::ser::{to_string_pretty, PrettyConfig};
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::genetic_algorithm::{
     operations::Individual,
     write_stat::{write_stat, InstanceStats},
 };
 
-use super::runner::Runnable;
+use super::{
+    runner::Runnable,
+    scoring::{FitnessScoring, Scoring},
+    selection::SelectionStrategy,
+};
 
-#[derive(Default)]
-pub struct Algorithm<T: Individual> {
+/// Generational algorithm, individuals are ranked by `scoring`
+pub struct Algorithm<T: Individual, S: Scoring<T> = FitnessScoring> {
     pub population: Vec<T>,
     pub offspring_population: Vec<T>,
+    scoring: S,
+    /// Shared by the individuals of the current run
+    context: T::Context,
+    /// Score of every individual of the population, best first
+    scores: Vec<f32>,
     max_generations: usize,
     max_no_improvement: usize,
     current_unbeat_best: (f32, usize),
@@ -25,40 +30,63 @@ pub struct Algorithm<T: Individual> {
 
     mutation_chance: f32,
     crossover_chance: f32,
+    selection_strategy: SelectionStrategy,
     population_size: usize,
     testing: bool,
     max_test_count: usize,
     instance_number: usize,
     testing_count: usize,
     instance_stats: InstanceStats,
+    /// Creatures of the generation being evaluated that were disqualified
+    generation_failures: usize,
+    /// Seed of the first execution, the following ones use the next seeds
+    seed: u64,
+    rng: StdRng,
 }
 
-impl<T: Individual + Default> Algorithm<T> {
+impl<T: Individual + Default, S: Scoring<T>> Algorithm<T, S> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         population_size: usize,
         max_generations: usize,
         max_no_improvement: usize,
         mutation_chance: f32,
         crossover_chance: f32,
+        selection_strategy: SelectionStrategy,
+        scoring: S,
         testing: bool,
         max_test_count: usize,
         instance_number: usize,
+        seed: u64,
     ) -> Self {
         Algorithm {
-            population_size,
+            population: Vec::new(),
+            offspring_population: Vec::new(),
+            scoring,
+            context: T::Context::default(),
+            scores: Vec::new(),
             max_generations,
             max_no_improvement,
+            current_unbeat_best: (0.0, 0),
+            new_population: Vec::new(),
+            current_generation: 0,
             mutation_chance,
             crossover_chance,
+            selection_strategy,
+            population_size,
             testing,
             max_test_count,
             instance_number,
-            ..Default::default()
+            testing_count: 0,
+            instance_stats: InstanceStats::default(),
+            generation_failures: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 }
 
-impl<T: Individual> Runnable<T> for Algorithm<T> {
+impl<T: Individual, S: Scoring<T>> Runnable<T> for Algorithm<T, S> {
     fn get_population_for_sim(&self) -> Vec<T> {
         self.population
             .iter()
@@ -76,40 +104,71 @@ impl<T: Individual> Runnable<T> for Algorithm<T> {
     }
 
     fn initialize_population(&mut self) {
-        self.instance_stats = InstanceStats::default();
         self.current_generation = 0;
         self.current_unbeat_best = (std::f32::MIN, 0);
         self.testing_count += 1;
 
+        let seed = self.seed.wrapping_add(self.testing_count as u64 - 1);
+        self.rng = StdRng::seed_from_u64(seed);
+        self.instance_stats = InstanceStats {
+            seed,
+            ..Default::default()
+        };
+        println!("Seed: {}", seed);
+        self.generation_failures = 0;
+
         self.offspring_population.clear();
         self.new_population.clear();
+        self.scoring.reset();
+        self.context = T::Context::default();
 
-        self.population = (0..self.population_size).map(|_| T::random()).collect();
+        let rng = &mut self.rng;
+        self.population = (0..self.population_size).map(|_| T::random(rng)).collect();
+        self.scores = vec![0.0; self.population_size];
     }
 
+    /// Keeps the individuals with the best scores
     fn selection(&mut self) {
-        self.population
-            .sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
+        let scores = self.scoring.scores(&self.population);
+
+        let mut ranked: Vec<(f32, T)> = scores.into_iter().zip(self.population.drain(..)).collect();
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        ranked.truncate(self.population_size);
 
-        self.population = self.population[0..self.population_size].to_vec();
+        let (scores, population) = ranked.into_iter().unzip();
+        self.scores = scores;
+        self.population = population;
     }
 
     fn reproduction(&mut self) {
         self.current_generation += 1;
         let mut offspring_population: Vec<T> = Vec::new();
 
-        for chunk in self.population.chunks(2) {
-            let (mut first_child, mut second_child) =
-                chunk[0].breed(&chunk[1], self.crossover_chance);
+        // With an odd population the last couple has one child too many
+        let couples = self.selection_strategy.couples(
+            &self.scores,
+            (self.population.len() + 1) / 2,
+            &mut self.rng,
+        );
 
-            first_child = first_child.mutate(self.mutation_chance);
-            second_child = second_child.mutate(self.mutation_chance);
-            first_child.correct();
-            second_child.correct();
+        for (first_parent, second_parent) in couples {
+            let (mut first_child, mut second_child) = self.population[first_parent].breed(
+                &self.population[second_parent],
+                self.crossover_chance,
+                &mut self.rng,
+            );
+
+            first_child =
+                first_child.mutate(self.mutation_chance, &mut self.context, &mut self.rng);
+            second_child =
+                second_child.mutate(self.mutation_chance, &mut self.context, &mut self.rng);
+            first_child.correct(&mut self.rng);
+            second_child.correct(&mut self.rng);
             offspring_population.push(first_child);
             offspring_population.push(second_child);
         }
 
+        offspring_population.truncate(self.population.len());
         self.offspring_population = offspring_population;
     }
 
@@ -118,9 +177,14 @@ impl<T: Individual> Runnable<T> for Algorithm<T> {
         self.population.append(&mut self.new_population);
 
         self.new_population.clear();
+        self.generation_failures = 0;
     }
 
-    fn finished_evaluating(&mut self, chromosome: T) {
+    fn finished_evaluating(&mut self, chromosome: T, failed: bool) {
+        if failed {
+            self.generation_failures += 1;
+        }
+
         if chromosome.get_fitness() > self.current_unbeat_best.0 {
             self.current_unbeat_best = (chromosome.get_fitness(), self.current_generation);
 
@@ -139,43 +203,40 @@ impl<T: Individual> Runnable<T> for Algorithm<T> {
     }
 
     fn save_results(&self, generation_count: usize) {
-        let pretty_config = PrettyConfig::default();
-        let best = self
-            .new_population
-            .iter()
-            .max_by(|x, y| x.get_fitness().partial_cmp(&y.get_fitness()).unwrap())
-            .unwrap();
-        let worst = self
-            .new_population
-            .iter()
-            .min_by(|x, y| x.get_fitness().partial_cmp(&y.get_fitness()).unwrap())
-            .unwrap();
-        let median = &self.new_population[self.new_population.len() / 2];
+        // Same statistics as the species based algorithm
+        let mut population: Vec<&T> = self.new_population.iter().collect();
+        population.sort_by(|a, b| {
+            b.get_fitness()
+                .partial_cmp(&a.get_fitness())
+                .unwrap_or(Ordering::Equal)
+        });
 
-        let mean = self
-            .new_population
-            .iter()
-            .map(|x| x.get_fitness())
-            .sum::<f32>()
-            / self.new_population.len() as f32;
-        let std_dev = self
-            .new_population
+        let best = population[0];
+        let worst = population[population.len() - 1];
+        let median = population[population.len() / 2];
+
+        let mean =
+            population.iter().map(|x| x.get_fitness()).sum::<f32>() / population.len() as f32;
+        let std_dev = (population
             .iter()
             .map(|x| (x.get_fitness() - mean).powi(2))
             .sum::<f32>()
-            / self.new_population.len() as f32;
+            / population.len() as f32)
+            .sqrt();
 
         let mean_string = format!("{:.2}", mean);
         let std_dev_string = format!("{:.2}", std_dev);
 
         println!(
-            "Generation {}: Best: {}, Worst: {}, Median: {}, Mean: {}, StdDev: {}",
+            "Generation {}: Best: {}, Worst: {}, Median: {}, Mean: {}, StdDev: {}, Failures: {}{}",
             generation_count,
             best.get_fitness(),
             worst.get_fitness(),
             median.get_fitness(),
             mean_string,
-            std_dev_string
+            std_dev_string,
+            self.generation_failures,
+            self.scoring.stats()
         );
 
         if self.testing {
@@ -194,6 +255,7 @@ impl<T: Individual> Runnable<T> for Algorithm<T> {
                 worst.get_fitness(),
                 mean,
                 std_dev,
+                self.generation_failures,
             );
 
             self.instance_stats.write(format!(
@@ -209,6 +271,40 @@ impl<T: Individual> Runnable<T> for Algorithm<T> {
 
     fn get_should_end(&self) -> bool {
         self.max_generations + 1 == self.current_generation
-            || self.current_generation - self.current_unbeat_best.1 > self.max_no_improvement
+            || (self.scoring.ends_without_improvement()
+                && self.current_generation - self.current_unbeat_best.1 > self.max_no_improvement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_algorithm::creature_chromosome::CreatureChromosome;
+
+    #[test]
+    fn test_odd_population_fills_a_generation() {
+        let mut algorithm = Algorithm::<CreatureChromosome>::new(
+            5,
+            10,
+            10,
+            0.1,
+            0.5,
+            SelectionStrategy::Truncation,
+            FitnessScoring,
+            false,
+            1,
+            1,
+            0,
+        );
+
+        algorithm.initialize_population();
+        algorithm.reproduction();
+        let population = algorithm.get_population_for_sim();
+        assert_eq!(population.len(), 10);
+
+        for chromosome in population {
+            algorithm.finished_evaluating(chromosome, false);
+        }
+        assert!(algorithm.all_have_finished_evaluating());
     }
 }
@@ -1,3 +1,6 @@
 pub mod algorithm;
+pub mod novelty_search;
 pub mod runner;
+pub mod scoring;
+pub mod selection;
 pub mod species_based_algorithm;
@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+
+use crate::{
+    config::CONFIG,
+    genetic_algorithm::operations::{Describable, Evaluatable},
+};
+
+use super::scoring::Scoring;
+
+/// Ranks individuals by how different their behaviour is from the rest of the
+/// population and from an archive of past novel behaviours, optionally mixed with fitness
+#[derive(Debug, Default, Clone)]
+pub struct NoveltySearch {
+    /// Behaviours of the most novel individuals of past generations
+    archive: Vec<Vec<f32>>,
+}
+
+impl<T: Evaluatable + Describable> Scoring<T> for NoveltySearch {
+    /// Archives the most novel individuals after scoring them
+    fn scores(&mut self, population: &[T]) -> Vec<f32> {
+        let behaviours: Vec<Vec<f32>> = population
+            .iter()
+            .map(|individual| individual.behaviour())
+            .collect();
+
+        let novelties: Vec<f32> = (0..behaviours.len())
+            .map(|index| {
+                let others: Vec<&Vec<f32>> = behaviours
+                    .iter()
+                    .enumerate()
+                    .filter(|(other_index, _)| *other_index != index)
+                    .map(|(_, behaviour)| behaviour)
+                    .chain(self.archive.iter())
+                    .collect();
+
+                novelty(&behaviours[index], &others, CONFIG.novelty_neighbours)
+            })
+            .collect();
+
+        let mut by_novelty: Vec<usize> = (0..novelties.len()).collect();
+        by_novelty.sort_by(|&a, &b| {
+            novelties[b]
+                .partial_cmp(&novelties[a])
+                .unwrap_or(Ordering::Equal)
+        });
+        for &index in by_novelty.iter().take(CONFIG.novelty_archive_additions) {
+            self.archive.push(behaviours[index].clone());
+        }
+
+        let fitnesses: Vec<f32> = population
+            .iter()
+            .map(|individual| individual.get_fitness())
+            .collect();
+
+        mix(&novelties, &fitnesses, CONFIG.novelty_fitness_weight)
+    }
+
+    fn reset(&mut self) {
+        self.archive.clear();
+    }
+
+    /// Pure novelty search ignores the fitness, so it never stagnates on it
+    fn ends_without_improvement(&self) -> bool {
+        CONFIG.novelty_fitness_weight > 0.0
+    }
+
+    fn stats(&self) -> String {
+        format!(", Archive: {}", self.archive.len())
+    }
+}
+
+fn behaviour_distance(behaviour: &[f32], other: &[f32]) -> f32 {
+    behaviour
+        .iter()
+        .zip(other.iter())
+        .map(|(value, other_value)| (value - other_value).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Mean distance to the `neighbours` closest behaviours
+fn novelty(behaviour: &[f32], others: &[&Vec<f32>], neighbours: usize) -> f32 {
+    let mut distances: Vec<f32> = others
+        .iter()
+        .map(|other| behaviour_distance(behaviour, other))
+        .collect();
+    distances.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    distances.truncate(neighbours.max(1));
+
+    if distances.is_empty() {
+        0.0
+    } else {
+        distances.iter().sum::<f32>() / distances.len() as f32
+    }
+}
+
+/// Mixes novelty and fitness, each relative to the highest one of the population
+fn mix(novelties: &[f32], fitnesses: &[f32], fitness_weight: f32) -> Vec<f32> {
+    let max_novelty = novelties.iter().fold(f32::EPSILON, |max, x| max.max(*x));
+    let max_fitness = fitnesses.iter().fold(f32::EPSILON, |max, x| max.max(*x));
+
+    novelties
+        .iter()
+        .zip(fitnesses.iter())
+        .map(|(novelty, fitness)| {
+            (1.0 - fitness_weight) * novelty / max_novelty + fitness_weight * fitness / max_fitness
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_novelty_is_distance_to_nearest_behaviours() {
+        let behaviours = vec![vec![0.0, 0.0], vec![1.0, 0.0], vec![0.0, 3.0]];
+        let others: Vec<&Vec<f32>> = behaviours.iter().collect();
+
+        assert!((novelty(&[0.0, 1.0], &others, 1) - 1.0).abs() < 1e-6);
+        assert!((novelty(&[0.0, 1.0], &others, 2) - (1.0 + 2.0_f32.sqrt()) / 2.0).abs() < 1e-6);
+        assert!(novelty(&[0.0, 0.0], &[], 3).abs() < f32::EPSILON);
+
+        let mixed = mix(&[2.0, 1.0], &[0.0, 4.0], 0.5);
+        assert!((mixed[0] - 0.5).abs() < 1e-6);
+        assert!((mixed[1] - 0.75).abs() < 1e-6);
+    }
+}
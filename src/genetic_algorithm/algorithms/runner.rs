@@ -19,8 +19,9 @@ pub trait Runnable<T: Individual> {
     /// Replaces the population with the new population based on the selection and reproduction
     fn replacement(&mut self);
 
-    /// Reportes a chromosome to the algorithm with fitness result
-    fn finished_evaluating(&mut self, chromosome: T);
+    /// Reportes a chromosome to the algorithm with fitness result,
+    /// `failed` tells if its simulation was disqualified
+    fn finished_evaluating(&mut self, chromosome: T, failed: bool);
 
     /// Returns true if all the population has been evaluated based on population_size
     fn all_have_finished_evaluating(&self) -> bool;
@@ -0,0 +1,33 @@
+use crate::genetic_algorithm::operations::Evaluatable;
+
+/// How individuals are ranked to survive and to be chosen as parents
+pub trait Scoring<T> {
+    /// Score of every individual of the population, the higher the better
+    fn scores(&mut self, population: &[T]) -> Vec<f32>;
+
+    /// Forgets what was learnt in a previous execution
+    fn reset(&mut self) {}
+
+    /// Whether a run ends when the best fitness stops improving
+    fn ends_without_improvement(&self) -> bool {
+        true
+    }
+
+    /// Added to the statistics printed every generation
+    fn stats(&self) -> String {
+        String::new()
+    }
+}
+
+/// Individuals are ranked by their fitness
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FitnessScoring;
+
+impl<T: Evaluatable> Scoring<T> for FitnessScoring {
+    fn scores(&mut self, population: &[T]) -> Vec<f32> {
+        population
+            .iter()
+            .map(|individual| individual.get_fitness())
+            .collect()
+    }
+}
@@ -0,0 +1,127 @@
+use rand::Rng;
+
+/// How parents are chosen among the individuals that survived a generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionStrategy {
+    /// Neighbours in fitness order breed together, the best with the second best
+    Truncation,
+    /// The fittest of `size` individuals taken at random
+    Tournament { size: usize },
+    /// Chance decreases linearly with the position in fitness order
+    LinearRank,
+    /// Chance proportional to fitness
+    Roulette,
+}
+
+impl SelectionStrategy {
+    /// Builds the strategy named in the command line arguments
+    pub fn from_options(name: &str, tournament_size: usize) -> SelectionStrategy {
+        match name {
+            "tournament" => SelectionStrategy::Tournament {
+                size: tournament_size.max(1),
+            },
+            "rank" => SelectionStrategy::LinearRank,
+            "roulette" => SelectionStrategy::Roulette,
+            _ => SelectionStrategy::Truncation,
+        }
+    }
+
+    /// Chooses `count` couples of parents, as indexes in `fitnesses`.
+    /// Fitnesses have to be sorted from best to worst. Truncation starts over
+    /// from the best when there are more couples than individuals
+    pub fn couples(
+        &self,
+        fitnesses: &[f32],
+        count: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<(usize, usize)> {
+        match self {
+            SelectionStrategy::Truncation if fitnesses.is_empty() => Vec::new(),
+            SelectionStrategy::Truncation => (0..count)
+                .map(|couple| {
+                    (
+                        2 * couple % fitnesses.len(),
+                        (2 * couple + 1) % fitnesses.len(),
+                    )
+                })
+                .collect(),
+            _ => (0..count)
+                .map(|_| (self.select(fitnesses, rng), self.select(fitnesses, rng)))
+                .collect(),
+        }
+    }
+
+    /// Chooses a single parent, as an index in `fitnesses`.
+    /// Fitnesses have to be sorted from best to worst
+    pub fn select(&self, fitnesses: &[f32], rng: &mut impl Rng) -> usize {
+        match self {
+            SelectionStrategy::Truncation => 0,
+            SelectionStrategy::Tournament { size } => (0..*size)
+                .map(|_| rng.gen_range(0..fitnesses.len()))
+                .min()
+                .unwrap_or(0),
+            SelectionStrategy::LinearRank => {
+                let weights: Vec<f32> = (0..fitnesses.len())
+                    .map(|rank| (fitnesses.len() - rank) as f32)
+                    .collect();
+                spin(&weights, rng)
+            }
+            SelectionStrategy::Roulette => {
+                let weights: Vec<f32> = fitnesses.iter().map(|fitness| fitness.max(0.0)).collect();
+                spin(&weights, rng)
+            }
+        }
+    }
+}
+
+/// Index chosen with a chance proportional to its weight,
+/// any of them if no weight is positive
+fn spin(weights: &[f32], rng: &mut impl Rng) -> usize {
+    let total: f32 = weights.iter().sum();
+
+    if total <= 0.0 || !total.is_finite() {
+        return rng.gen_range(0..weights.len());
+    }
+
+    let mut target = rng.gen::<f32>() * total;
+    for (index, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return index;
+        }
+        target -= weight;
+    }
+
+    weights.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_selection_prefers_the_fittest() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let fitnesses = [10.0, 5.0, 1.0, 0.0];
+
+        assert_eq!(
+            SelectionStrategy::Truncation.couples(&fitnesses, 2, &mut rng),
+            vec![(0, 1), (2, 3)]
+        );
+
+        for strategy in [
+            SelectionStrategy::Tournament { size: 3 },
+            SelectionStrategy::LinearRank,
+            SelectionStrategy::Roulette,
+        ] {
+            let mut picks = [0; 4];
+            for _ in 0..1000 {
+                picks[strategy.select(&fitnesses, &mut rng)] += 1;
+            }
+
+            assert!(picks[0] > picks[2], "{:?} picked {:?}", strategy, picks);
+            assert!(picks[1] > picks[3], "{:?} picked {:?}", strategy, picks);
+        }
+    }
+}
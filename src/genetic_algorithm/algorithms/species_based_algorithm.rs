@@ -1,20 +1,32 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
 
-use bevy::log::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
     config::CONFIG,
     genetic_algorithm::{
-        operations::{Individual, Selective},
+        operations::{Evaluatable, Individual, Selective},
         write_stat::{write_stat, InstanceStats},
     },
 };
 
-use super::runner::Runnable;
+use super::{runner::Runnable, selection::SelectionStrategy};
 
-#[derive(Default)]
 pub struct SpeciesBasedAlgorithm<T: Individual + Selective> {
-    pub population: HashMap<usize, Vec<T>>,
+    /// Members of every species by species number.
+    /// Species are kept sorted so they are always bred in the same order
+    pub population: BTreeMap<usize, Vec<T>>,
+    /// Individual every species is compared against when assigning species
+    representatives: BTreeMap<usize, T>,
+    next_species: usize,
+    /// Individuals closer than this to a representative belong to its species
+    compatibility_threshold: f32,
+    /// Shared by the individuals of the current run
+    context: T::Context,
     pub offspring_population: Vec<T>,
     max_generations: usize,
     max_no_improvement: usize,
@@ -24,55 +36,237 @@ pub struct SpeciesBasedAlgorithm<T: Individual + Selective> {
     current_generation: usize,
     mutation_chance: f32,
     crossover_chance: f32,
+    selection_strategy: SelectionStrategy,
     population_size: usize,
     testing: bool,
     max_test_count: usize,
     instance_number: usize,
     testing_count: usize,
     instance_stats: InstanceStats,
+    /// Creatures of the generation being evaluated that were disqualified
+    generation_failures: usize,
+    /// Seed of the first execution, the following ones use the next seeds
+    seed: u64,
+    rng: StdRng,
 }
 
 impl<T: Individual + Selective> SpeciesBasedAlgorithm<T> {
+    /// Assigns every individual to the first species whose representative is close
+    /// enough, or to a new species. Then picks the new representatives and moves
+    /// the threshold towards the target species count
+    fn speciate(&mut self, individuals: Vec<T>) {
+        self.population.clear();
+
+        for individual in individuals {
+            let species = match self.representatives.iter().find(|(_, representative)| {
+                representative.distance(&individual) < self.compatibility_threshold
+            }) {
+                Some((species, _)) => *species,
+                None => {
+                    let species = self.next_species;
+                    self.next_species += 1;
+                    self.representatives.insert(species, individual.clone());
+                    species
+                }
+            };
+
+            self.population
+                .entry(species)
+                .or_insert_with(Vec::new)
+                .push(individual);
+        }
+
+        let rng = &mut self.rng;
+        self.representatives = self
+            .population
+            .iter()
+            .map(|(species, members)| (*species, members[rng.gen_range(0..members.len())].clone()))
+            .collect();
+
+        let population = &self.population;
+        self.previous_best_by_species
+            .retain(|species, _| population.contains_key(species));
+
+        if CONFIG.target_species_count > 0 {
+            match self.population.len().cmp(&CONFIG.target_species_count) {
+                Ordering::Greater => {
+                    self.compatibility_threshold += CONFIG.compatibility_threshold_step
+                }
+                Ordering::Less => {
+                    self.compatibility_threshold = (self.compatibility_threshold
+                        - CONFIG.compatibility_threshold_step)
+                        .max(CONFIG.compatibility_threshold_step)
+                }
+                Ordering::Equal => {}
+            }
+        }
+    }
+
+    /// Counts the generations every species has gone without improving its best fitness
+    fn update_stagnation(&mut self) {
+        for (species, members) in self.population.iter() {
+            let best = best_fitness(members);
+
+            match self.previous_best_by_species.get_mut(species) {
+                Some(previous) if best <= previous.0 + CONFIG.improvement_threshold => {
+                    previous.1 += 1;
+                }
+                _ => {
+                    self.previous_best_by_species.insert(*species, (best, 0));
+                }
+            }
+        }
+    }
+
+    /// Removes the species that haven't improved for `max_unchanged_generations`,
+    /// except the one with the best individual. Their offspring go to the other species
+    fn cull_stagnant_species(&mut self) {
+        let best_species = self
+            .population
+            .iter()
+            .map(|(species, members)| (*species, best_fitness(members)))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+            .map(|(species, _)| species);
+
+        let stagnant: Vec<usize> = self
+            .previous_best_by_species
+            .iter()
+            .filter(|(species, (_, unchanged))| {
+                *unchanged > CONFIG.max_unchanged_generations && Some(**species) != best_species
+            })
+            .map(|(species, _)| *species)
+            .collect();
+
+        for species in stagnant {
+            self.population.remove(&species);
+            self.representatives.remove(&species);
+            self.previous_best_by_species.remove(&species);
+        }
+    }
+
+    /// Sum of the shared fitnesses of the members of every species: fitnesses are
+    /// shifted so the worst individual has 0, then divided by the size of the species
+    fn species_shares(&self) -> Vec<f32> {
+        let min_fitness = lowest_fitness(self.population.values().flatten());
+
+        self.population
+            .values()
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|member| member.get_fitness() - min_fitness)
+                    .sum::<f32>()
+                    / members.len() as f32
+            })
+            .collect()
+    }
+
     fn produce_children(
         first_parent: &T,
         second_parent: &T,
         offspring_population: &mut Vec<T>,
         mutation_chance: f32,
         crossover_chance: f32,
+        context: &mut T::Context,
+        rng: &mut impl Rng,
     ) {
         let (mut first_child, mut second_child) =
-            first_parent.breed(second_parent, crossover_chance);
+            first_parent.breed(second_parent, crossover_chance, rng);
 
-        first_child = first_child.mutate(mutation_chance);
-        second_child = second_child.mutate(mutation_chance);
-        first_child.correct();
-        second_child.correct();
+        first_child = first_child.mutate(mutation_chance, context, rng);
+        second_child = second_child.mutate(mutation_chance, context, rng);
+        first_child.correct(rng);
+        second_child.correct(rng);
         offspring_population.push(first_child);
         offspring_population.push(second_child);
     }
 }
 
+fn best_fitness<T: Evaluatable>(members: &[T]) -> f32 {
+    members
+        .iter()
+        .map(|member| member.get_fitness())
+        .fold(f32::MIN, f32::max)
+}
+
+fn lowest_fitness<'a, T: Evaluatable + 'a>(individuals: impl Iterator<Item = &'a T>) -> f32 {
+    individuals
+        .map(|individual| individual.get_fitness())
+        .fold(f32::MAX, f32::min)
+}
+
+/// Splits `total` offspring between species proportionally to their shares,
+/// what is left after rounding down goes to the biggest fractions
+fn offspring_quotas(shares: &[f32], total: usize) -> Vec<usize> {
+    if shares.is_empty() {
+        return Vec::new();
+    }
+
+    let sum: f32 = shares.iter().sum();
+    let exact: Vec<f32> = if sum > 0.0 && sum.is_finite() {
+        shares
+            .iter()
+            .map(|share| share / sum * total as f32)
+            .collect()
+    } else {
+        vec![total as f32 / shares.len() as f32; shares.len()]
+    };
+
+    let mut quotas: Vec<usize> = exact.iter().map(|quota| quota.floor() as usize).collect();
+    let mut by_fraction: Vec<usize> = (0..shares.len()).collect();
+    by_fraction.sort_by(|&a, &b| {
+        (exact[b] - exact[b].floor())
+            .partial_cmp(&(exact[a] - exact[a].floor()))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let missing = total.saturating_sub(quotas.iter().sum());
+    for index in by_fraction.into_iter().cycle().take(missing) {
+        quotas[index] += 1;
+    }
+
+    quotas
+}
+
 impl<T: Individual + Selective + Default> SpeciesBasedAlgorithm<T> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         population_size: usize,
         max_generations: usize,
         max_no_improvement: usize,
         mutation_chance: f32,
         crossover_chance: f32,
+        selection_strategy: SelectionStrategy,
         testing: bool,
         max_test_count: usize,
         instance_number: usize,
+        seed: u64,
     ) -> Self {
         SpeciesBasedAlgorithm {
-            population_size,
+            population: BTreeMap::new(),
+            representatives: BTreeMap::new(),
+            next_species: 0,
+            compatibility_threshold: CONFIG.compatibility_threshold,
+            context: T::Context::default(),
+            offspring_population: Vec::new(),
             max_generations,
             max_no_improvement,
+            current_unbeat_best: (0.0, 0),
+            previous_best_by_species: HashMap::new(),
+            new_population: Vec::new(),
+            current_generation: 0,
             mutation_chance,
             crossover_chance,
+            selection_strategy,
+            population_size,
             testing,
             max_test_count,
             instance_number,
-            ..Default::default()
+            testing_count: 0,
+            instance_stats: InstanceStats::default(),
+            generation_failures: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 }
@@ -96,145 +290,141 @@ impl<T: Individual + Selective + fmt::Debug> Runnable<T> for SpeciesBasedAlgorit
     }
 
     fn initialize_population(&mut self) {
-        self.instance_stats = InstanceStats::default();
         self.current_generation = 0;
         self.testing_count += 1;
+
+        let seed = self.seed.wrapping_add(self.testing_count as u64 - 1);
+        self.rng = StdRng::seed_from_u64(seed);
+        self.instance_stats = InstanceStats {
+            seed,
+            ..Default::default()
+        };
+        println!("Seed: {}", seed);
+        self.generation_failures = 0;
+
         self.current_unbeat_best = (std::f32::MIN, 0);
         self.population.clear();
         self.offspring_population.clear();
         self.new_population.clear();
         self.previous_best_by_species.clear();
+        self.representatives.clear();
+        self.next_species = 0;
+        self.compatibility_threshold = CONFIG.compatibility_threshold;
+        self.context = T::Context::default();
 
-        let initial_population: Vec<T> = (0..self.population_size).map(|_| T::random()).collect();
+        let rng = &mut self.rng;
+        let initial_population: Vec<T> =
+            (0..self.population_size).map(|_| T::random(rng)).collect();
 
-        for chromosome in initial_population {
-            self.population
-                .entry(chromosome.characteristic())
-                .or_insert_with(Vec::new)
-                .push(chromosome);
-        }
+        self.speciate(initial_population);
     }
 
+    /// Keeps the best individuals by shared fitness, so crowded species don't take
+    /// over the population. The best individual of every species always survives
     fn selection(&mut self) {
-        let population = self.population.clone();
-        self.population.clear();
-        let mut flatten_population = population.values().flatten().collect::<Vec<&T>>();
-
-        flatten_population.sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
-        flatten_population.truncate(self.population_size);
+        let population = std::mem::take(&mut self.population);
+        let min_fitness = lowest_fitness(population.values().flatten());
+
+        let mut ranked: Vec<(usize, bool, f32, T)> = Vec::new();
+        for (species, members) in population {
+            let champion = best_fitness(&members);
+            let species_size = members.len() as f32;
+            let mut champion_found = false;
+
+            for member in members {
+                let is_champion = !champion_found && member.get_fitness() >= champion;
+                champion_found |= is_champion;
+                let shared_fitness = (member.get_fitness() - min_fitness) / species_size;
+                ranked.push((species, is_champion, shared_fitness, member));
+            }
+        }
 
-        for individual in flatten_population {
+        ranked.sort_by(|a, b| {
+            b.1.cmp(&a.1).then_with(|| {
+                let (a, b) = if a.1 {
+                    (a.3.get_fitness(), b.3.get_fitness())
+                } else {
+                    (a.2, b.2)
+                };
+                b.partial_cmp(&a).unwrap_or(Ordering::Equal)
+            })
+        });
+        ranked.truncate(self.population_size);
+
+        for (species, _, _, individual) in ranked {
             self.population
-                .entry(individual.characteristic())
+                .entry(species)
                 .or_insert_with(Vec::new)
-                .push(individual.clone());
+                .push(individual);
+        }
+
+        for members in self.population.values_mut() {
+            members.sort_by(|a, b| {
+                b.get_fitness()
+                    .partial_cmp(&a.get_fitness())
+                    .unwrap_or(Ordering::Equal)
+            });
         }
     }
 
+    /// Every species gets a number of offspring proportional to its shared fitness
     // TODO: We are cloning every child multiple times
     fn reproduction(&mut self) {
         self.current_generation += 1;
 
-        let mut offspring_population: Vec<T> = Vec::new();
+        self.update_stagnation();
+        self.cull_stagnant_species();
 
-        // Create reference to elements ordered by fitness
-        // Will be used for breeding when a species has not improved for a while
-        let population = self.population.clone();
-
-        let mut population: Vec<&T> = population.values().flatten().collect();
-        population.sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
-
-        for (species_size, species) in self.population.iter_mut() {
-            // If the species has not improved for a while, breed it with the best individual
-            // In the first run there is no best_by_species
-            if !self.previous_best_by_species.is_empty()
-                && self
-                    .previous_best_by_species
-                    .get(species_size)
-                    .unwrap_or(&(0.0, 0))
-                    .1
-                    > CONFIG.max_unchanged_generations
-            {
-                for (first_parent, second_parent) in species
-                    .iter()
-                    .zip(population.iter())
-                    .take(species.len() / 2)
-                {
-                    SpeciesBasedAlgorithm::<T>::produce_children(
-                        first_parent,
-                        second_parent,
-                        &mut offspring_population,
-                        self.mutation_chance,
-                        self.crossover_chance,
-                    );
-                }
-            } else {
-                if self
-                    .previous_best_by_species
-                    .entry(*species_size)
-                    .or_insert((0.0, 0))
-                    .0
-                    < species[0].get_fitness() + CONFIG.improvement_threshold
-                {
-                    self.previous_best_by_species
-                        .insert(*species_size, (species[0].get_fitness(), 0));
-                } else {
-                    self.previous_best_by_species
-                        .entry(*species_size)
-                        .and_modify(|entry| {
-                            entry.1 += 1;
-                        });
-                }
+        let parent_count: usize = self.population.values().map(Vec::len).sum();
+        let quotas = offspring_quotas(
+            &self.species_shares(),
+            (self.population_size * 2).saturating_sub(parent_count),
+        );
 
-                // Breed the species
-                for parents in species.chunks(2) {
-                    if parents.len() < 2 {
-                        continue;
-                    }
-
-                    let first_parent = &parents[0];
-                    let second_parent = &parents[1];
-
-                    SpeciesBasedAlgorithm::<T>::produce_children(
-                        first_parent,
-                        second_parent,
-                        &mut offspring_population,
-                        self.mutation_chance,
-                        self.crossover_chance,
-                    );
-                }
-            }
+        let mut offspring_population: Vec<T> = Vec::new();
 
-            if species.len() % 2 != 0 {
-                let first_parent = &species.last().unwrap();
-                let second_parent = population[species.len() - 1];
+        for (species, quota) in self.population.values().zip(quotas) {
+            let species_fitnesses: Vec<f32> = species
+                .iter()
+                .map(|individual| individual.get_fitness())
+                .collect();
 
+            // Breed the species, the last couple may have one child too many
+            let couples =
+                self.selection_strategy
+                    .couples(&species_fitnesses, (quota + 1) / 2, &mut self.rng);
+
+            let mut species_offspring: Vec<T> = Vec::new();
+            for (first_parent, second_parent) in couples {
                 SpeciesBasedAlgorithm::<T>::produce_children(
-                    first_parent,
-                    second_parent,
-                    &mut offspring_population,
+                    &species[first_parent],
+                    &species[second_parent],
+                    &mut species_offspring,
                     self.mutation_chance,
                     self.crossover_chance,
+                    &mut self.context,
+                    &mut self.rng,
                 );
             }
+
+            species_offspring.truncate(quota);
+            offspring_population.extend(species_offspring);
         }
 
-        offspring_population.truncate(self.population.values().flatten().count());
         self.offspring_population = offspring_population;
     }
 
     fn replacement(&mut self) {
-        self.population.clear();
-        for chromosome in self.new_population.iter() {
-            self.population
-                .entry(chromosome.characteristic())
-                .or_insert_with(Vec::new)
-                .push(chromosome.clone());
-        }
-        self.new_population.clear();
+        let new_population = std::mem::take(&mut self.new_population);
+        self.speciate(new_population);
+        self.generation_failures = 0;
     }
 
-    fn finished_evaluating(&mut self, chromosome: T) {
+    fn finished_evaluating(&mut self, chromosome: T, failed: bool) {
+        if failed {
+            self.generation_failures += 1;
+        }
+
         if chromosome.get_fitness() > self.current_unbeat_best.0 {
             self.current_unbeat_best = (chromosome.get_fitness(), self.current_generation);
 
@@ -254,7 +444,11 @@ impl<T: Individual + Selective + fmt::Debug> Runnable<T> for SpeciesBasedAlgorit
 
     fn save_results(&self, generation_count: usize) {
         let mut population: Vec<T> = self.new_population.clone();
-        population.sort_by(|a, b| b.get_fitness().partial_cmp(&a.get_fitness()).unwrap());
+        population.sort_by(|a, b| {
+            b.get_fitness()
+                .partial_cmp(&a.get_fitness())
+                .unwrap_or(Ordering::Equal)
+        });
 
         let best = population[0].clone();
         let worst = population.last().unwrap();
@@ -273,13 +467,14 @@ impl<T: Individual + Selective + fmt::Debug> Runnable<T> for SpeciesBasedAlgorit
         let std_dev_string = format!("{:.2}", std_dev);
 
         println!(
-            "Generation {}: Best: {}, Worst: {}, Median: {}, Mean: {}, StdDev: {}",
+            "Generation {}: Best: {}, Worst: {}, Median: {}, Mean: {}, StdDev: {}, Failures: {}",
             generation_count,
             best.get_fitness(),
             worst.get_fitness(),
             median.get_fitness(),
             mean_string,
-            std_dev_string
+            std_dev_string,
+            self.generation_failures
         );
 
         if self.testing {
@@ -298,6 +493,7 @@ impl<T: Individual + Selective + fmt::Debug> Runnable<T> for SpeciesBasedAlgorit
                 worst.get_fitness(),
                 mean,
                 std_dev,
+                self.generation_failures,
             );
 
             self.instance_stats.write(format!(
@@ -316,3 +512,16 @@ impl<T: Individual + Selective + fmt::Debug> Runnable<T> for SpeciesBasedAlgorit
             || self.current_generation - self.current_unbeat_best.1 > self.max_no_improvement
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offspring_quotas_follow_shares() {
+        assert_eq!(offspring_quotas(&[3.0, 1.0], 8), vec![6, 2]);
+        assert_eq!(offspring_quotas(&[1.0, 1.0, 1.0], 10), vec![4, 3, 3]);
+        assert_eq!(offspring_quotas(&[0.0, 0.0], 5).iter().sum::<usize>(), 5);
+        assert!(offspring_quotas(&[], 5).is_empty());
+    }
+}
@@ -0,0 +1,26 @@
+use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// What a creature did during its evaluation, creatures that moved alike
+/// have behaviours close to each other
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Behaviour {
+    /// Position of the creature when the evaluation ended
+    pub final_position: Vec2,
+    /// Highest the creature's position got
+    pub max_height: f32,
+    /// Fraction of the time its nodes spent touching the ground
+    pub ground_contact: f32,
+}
+
+impl Behaviour {
+    /// Values that describe the behaviour, compared by euclidean distance
+    pub fn descriptor(&self) -> Vec<f32> {
+        vec![
+            self.final_position.x,
+            self.final_position.y,
+            self.max_height,
+            self.ground_contact,
+        ]
+    }
+}
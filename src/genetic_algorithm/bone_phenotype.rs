@@ -0,0 +1,32 @@
+use super::operations::{Crossable, RandomCreatable};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Represents a rigid connection between two nodes.
+/// Keeps the nodes at the distance they start at
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct BonePhenotype {
+    /// The nodes this bone joins
+    pub nodes: (usize, usize),
+}
+
+impl Crossable for BonePhenotype {
+    /// Crosses two BonePhenotypes. Verify that nodes are present in both parents
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
+        BonePhenotype {
+            nodes: if rng.gen::<f32>() > chance {
+                self.nodes
+            } else {
+                other.nodes
+            },
+        }
+    }
+}
+
+impl RandomCreatable for BonePhenotype {
+    fn random(rng: &mut impl Rng) -> Self {
+        BonePhenotype {
+            nodes: (rng.gen_range(0..20), rng.gen_range(0..20)),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use crate::config::CONFIG;
+
+use super::operations::{Correctable, Crossable, Mutable, RandomCreatable};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Inputs of every hidden neuron: sine and cosine of the internal clock's phase, and a bias
+pub const CLOCK_INPUTS: usize = 3;
+/// Inputs only the output of a muscle reads: its own length relative to its range,
+/// whether each of its nodes touches the ground, a bias, and sine and cosine
+/// of the phase of the clock it follows
+pub const MUSCLE_INPUTS: usize = 6;
+
+/// Hidden layer of the neural network that drives the muscles of a creature.
+/// The output weights belong to every MusclePhenotype, so muscles can be
+/// added and removed without changing the rest of the network
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ControllerPhenotype {
+    /// Weights from the clock inputs to every hidden neuron
+    pub hidden_weights: Vec<Vec<f32>>,
+}
+
+impl Correctable for ControllerPhenotype {
+    fn correct(&mut self, _rng: &mut impl Rng) {
+        self.hidden_weights
+            .iter_mut()
+            .for_each(|weights| correct_weights(weights));
+    }
+
+    fn is_correct(&self) -> bool {
+        self.hidden_weights
+            .iter()
+            .all(|weights| are_weights_correct(weights))
+    }
+}
+
+impl Crossable for ControllerPhenotype {
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
+        ControllerPhenotype {
+            hidden_weights: self
+                .hidden_weights
+                .iter()
+                .enumerate()
+                .map(|(index, weights)| match other.hidden_weights.get(index) {
+                    Some(other_weights) => cross_weights(weights, other_weights, chance, rng),
+                    None => weights.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Mutable for ControllerPhenotype {
+    /// Mutates the weights, neurons missing from the config are added
+    fn mutate(&self, chance: f32, rng: &mut impl Rng) -> Self {
+        let mut hidden_weights = self.hidden_weights.clone();
+        hidden_weights.resize(CONFIG.controller_hidden_neurons, Vec::new());
+
+        ControllerPhenotype {
+            hidden_weights: hidden_weights
+                .iter()
+                .map(|weights| mutate_weights(weights, CLOCK_INPUTS, chance, rng))
+                .collect(),
+        }
+    }
+}
+
+impl RandomCreatable for ControllerPhenotype {
+    fn random(rng: &mut impl Rng) -> Self {
+        ControllerPhenotype {
+            hidden_weights: (0..CONFIG.controller_hidden_neurons)
+                .map(|_| random_weights(CLOCK_INPUTS, rng))
+                .collect(),
+        }
+    }
+}
+
+/// Amount of weights the output of every muscle has
+pub fn muscle_weight_count() -> usize {
+    CONFIG.controller_hidden_neurons + MUSCLE_INPUTS
+}
+
+pub fn random_weights(count: usize, rng: &mut impl Rng) -> Vec<f32> {
+    (0..count).map(|_| rng.gen_range(-1.0..1.0)).collect()
+}
+
+/// Takes every weight from either parent, weights only one of them has are kept
+pub fn cross_weights(weights: &[f32], other: &[f32], chance: f32, rng: &mut impl Rng) -> Vec<f32> {
+    weights
+        .iter()
+        .enumerate()
+        .map(|(index, weight)| match other.get(index) {
+            Some(other_weight) if rng.gen::<f32>() <= chance => *other_weight,
+            _ => *weight,
+        })
+        .collect()
+}
+
+/// Nudges the weights, missing ones are created at random
+pub fn mutate_weights(weights: &[f32], count: usize, chance: f32, rng: &mut impl Rng) -> Vec<f32> {
+    let mut weights = weights.to_vec();
+    while weights.len() < count {
+        weights.push(rng.gen_range(-1.0..1.0));
+    }
+
+    for weight in weights.iter_mut() {
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            *weight = (*weight + rng.gen::<f32>() - 0.5)
+                .clamp(-CONFIG.max_controller_weight, CONFIG.max_controller_weight);
+        }
+    }
+
+    weights
+}
+
+pub fn correct_weights(weights: &mut [f32]) {
+    for weight in weights.iter_mut() {
+        *weight = weight.clamp(-CONFIG.max_controller_weight, CONFIG.max_controller_weight);
+    }
+}
+
+pub fn are_weights_correct(weights: &[f32]) -> bool {
+    weights
+        .iter()
+        .all(|weight| weight.abs() <= CONFIG.max_controller_weight)
+}
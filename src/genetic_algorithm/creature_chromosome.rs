@@ -1,28 +1,108 @@
+use std::cmp::Ordering;
+
+use bevy::math::Vec2;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::{config::CONFIG, genetic_algorithm::*};
+use crate::{
+    config::{MuscleActuation, CONFIG},
+    genetic_algorithm::*,
+};
 
 use super::{
-    muscle_phenotype::MusclePhenotype,
+    behaviour::Behaviour,
+    bone_phenotype::BonePhenotype,
+    controller_phenotype::ControllerPhenotype,
+    muscle_phenotype::{MusclePhenotype, Trigger},
+    neat_phenotype::{Innovations, NeatPhenotype},
     node_phenotype::NodePhenotype,
     operations::{
-        Breedable, Correctable, Crossable, Evaluatable, Individual, Mutable, RandomCreatable,
-        Selective,
+        Breedable, Correctable, Crossable, Describable, Evaluatable, Individual, Mutable,
+        RandomCreatable, Selective,
     },
 };
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CreatureChromosome {
     /// The internal clock goes from 0 to this value
     pub internal_clock_size: f32,
+    /// Sizes of the clocks after the internal one, every muscle follows one of the clocks
+    #[serde(default)]
+    pub extra_clock_sizes: Vec<f32>,
     pub fitness: f32,
+    /// What the creature did in its last evaluation
+    #[serde(default)]
+    pub behaviour: Behaviour,
     pub nodes: Vec<node_phenotype::NodePhenotype>,
     pub muscles: Vec<muscle_phenotype::MusclePhenotype>,
+    /// Rigid connections, nodes can be joined by muscles, bones or both
+    #[serde(default)]
+    pub bones: Vec<bone_phenotype::BonePhenotype>,
+    /// Only used when muscles are driven by a neural network
+    #[serde(default)]
+    pub controller: ControllerPhenotype,
+    /// Only used when muscles are driven by a NEAT network,
+    /// otherwise it is left empty
+    #[serde(default)]
+    pub neat_controller: NeatPhenotype,
+}
+
+impl Default for CreatureChromosome {
+    fn default() -> Self {
+        CreatureChromosome {
+            internal_clock_size: CONFIG.min_internal_clock_size,
+            extra_clock_sizes: Vec::new(),
+            fitness: 0.0,
+            behaviour: Behaviour::default(),
+            nodes: Vec::new(),
+            muscles: Vec::new(),
+            bones: Vec::new(),
+            controller: ControllerPhenotype::default(),
+            neat_controller: NeatPhenotype::default(),
+        }
+    }
 }
 
 impl CreatureChromosome {
-    /// Fixes muscle node references
+    /// Sizes of every clock, starting with the internal one
+    pub fn clock_sizes(&self) -> Vec<f32> {
+        std::iter::once(self.internal_clock_size)
+            .chain(self.extra_clock_sizes.iter().copied())
+            .collect()
+    }
+
+    /// Whether the creature has nodes and every muscle, bone and trigger
+    /// refers to one of them
+    fn references_existing_nodes(&self) -> bool {
+        let exists = |node: usize| node < self.nodes.len();
+
+        !self.nodes.is_empty()
+            && self.muscles.iter().all(|muscle| {
+                exists(muscle.nodes.0)
+                    && exists(muscle.nodes.1)
+                    && match muscle.trigger {
+                        Trigger::GroundContact { node } => exists(node),
+                        Trigger::Clock => true,
+                    }
+            })
+            && self
+                .bones
+                .iter()
+                .all(|bone| exists(bone.nodes.0) && exists(bone.nodes.1))
+    }
+
+    /// Makes muscles follow clocks that exist
+    fn fix_clock_references(&mut self, rng: &mut impl Rng) {
+        let clock_count = self.extra_clock_sizes.len() + 1;
+
+        for muscle in self.muscles.iter_mut() {
+            if muscle.clock >= clock_count {
+                muscle.clock = rng.gen_range(0..clock_count);
+            }
+        }
+    }
+
+    /// Fixes muscle and bone node references
     ///
     /// # Example
     /// ```
@@ -38,35 +118,26 @@ impl CreatureChromosome {
     ///  ..Default::default()
     /// };
     /// ```
-    fn fix_muscles_node_references(&mut self) {
-        for muscle in self.muscles.iter_mut() {
-            let (node1, node2) = muscle.nodes;
-            if (node1 as usize) >= self.nodes.len() {
-                let mut rng = rand::thread_rng();
-                muscle.nodes.0 = rng.gen_range(0..self.nodes.len());
-            }
+    fn fix_node_references(&mut self, rng: &mut impl Rng) {
+        let node_count = self.nodes.len();
 
-            if (node2 as usize) >= self.nodes.len() {
-                let mut rng = rand::thread_rng();
-                muscle.nodes.1 = rng.gen_range(0..self.nodes.len());
-            }
-
-            if node1 == node2 {
-                let mut rng = rand::thread_rng();
+        for muscle in self.muscles.iter_mut() {
+            fix_connection(&mut muscle.nodes, node_count, rng);
 
-                loop {
-                    let node = rng.gen_range(0..self.nodes.len());
-                    if node != node1 {
-                        muscle.nodes.1 = node;
-                        break;
-                    }
+            if let Trigger::GroundContact { node } = &mut muscle.trigger {
+                if *node >= node_count {
+                    *node = rng.gen_range(0..node_count);
                 }
             }
         }
+
+        for bone in self.bones.iter_mut() {
+            fix_connection(&mut bone.nodes, node_count, rng);
+        }
     }
 
     /// Connects dangling nodes to the nearest present node
-    fn fix_danging_nodes(&mut self) {
+    fn fix_danging_nodes(&mut self, rng: &mut impl Rng) {
         let mut visited: Vec<usize> = Vec::new();
         let mut graph_components: Vec<usize> = vec![0; self.nodes.len()];
 
@@ -84,7 +155,7 @@ impl CreatureChromosome {
                 continue;
             }
 
-            let connection = self.connect_to_closest(i, &mut visited);
+            let connection = self.connect_to_closest(i, &mut visited, rng);
 
             // Assign new component to every element inside the same component of the graph
             for (node_index, component) in graph_components.clone().iter().enumerate() {
@@ -121,11 +192,17 @@ impl CreatureChromosome {
         component_index[node_index] = component;
         visited.push(node_index);
 
-        for muscle in self.muscles.iter() {
-            if muscle.nodes.0 == node_index {
-                self.tag_graph_components(muscle.nodes.1, visited, component_index, component);
-            } else if muscle.nodes.1 == node_index {
-                self.tag_graph_components(muscle.nodes.0, visited, component_index, component);
+        let connections = self
+            .muscles
+            .iter()
+            .map(|muscle| muscle.nodes)
+            .chain(self.bones.iter().map(|bone| bone.nodes));
+
+        for nodes in connections {
+            if nodes.0 == node_index {
+                self.tag_graph_components(nodes.1, visited, component_index, component);
+            } else if nodes.1 == node_index {
+                self.tag_graph_components(nodes.0, visited, component_index, component);
             }
         }
     }
@@ -136,8 +213,14 @@ impl CreatureChromosome {
     ///
     /// * `node_index` - Index of the node to be connected
     /// * `visited` - Vector of connected nodes' index
+    /// * `rng` - Random number generator for the new muscle
     ///
-    fn connect_to_closest(&mut self, node_index: usize, connected: &mut Vec<usize>) -> usize {
+    fn connect_to_closest(
+        &mut self,
+        node_index: usize,
+        connected: &mut Vec<usize>,
+        rng: &mut impl Rng,
+    ) -> usize {
         let node = &self.nodes[node_index];
         let mut closest_node_index = 0;
         let mut closest_node_distance = f32::MAX;
@@ -157,7 +240,7 @@ impl CreatureChromosome {
             closest_node_index = *other_node;
         }
 
-        let mut muscle = MusclePhenotype::random();
+        let mut muscle = MusclePhenotype::random(rng);
         muscle.nodes = (node_index, closest_node_index);
 
         connected.push(node_index);
@@ -165,111 +248,428 @@ impl CreatureChromosome {
 
         closest_node_index
     }
+
+    /// Nodes joined by a muscle or a bone
+    fn connections(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.muscles
+            .iter()
+            .map(|muscle| muscle.nodes)
+            .chain(self.bones.iter().map(|bone| bone.nodes))
+    }
+
+    /// Up to `size` nodes connected to `start`, nearest ones first
+    fn connected_nodes(&self, start: usize, size: usize) -> Vec<usize> {
+        let mut nodes = vec![start];
+        let mut next = 0;
+
+        while next < nodes.len() && nodes.len() < size {
+            let node = nodes[next];
+            next += 1;
+
+            for (node1, node2) in self.connections() {
+                let neighbour = if node1 == node {
+                    node2
+                } else if node2 == node {
+                    node1
+                } else {
+                    continue;
+                };
+
+                if nodes.len() < size && neighbour < self.nodes.len() && !nodes.contains(&neighbour)
+                {
+                    nodes.push(neighbour);
+                }
+            }
+        }
+
+        nodes
+    }
+
+    fn centroid(&self, nodes: &[usize]) -> Vec2 {
+        nodes
+            .iter()
+            .map(|&node| self.nodes[node].position)
+            .fold(Vec2::ZERO, |sum, position| sum + position)
+            / nodes.len() as f32
+    }
+
+    /// Body of `self` with a connected part replaced by a connected part of `other`,
+    /// placed where the removed one was. Muscles and bones that joined the removed
+    /// part join the part that replaces it instead, so the body stays connected
+    fn exchange_subgraph(
+        &self,
+        other: &Self,
+        rng: &mut impl Rng,
+    ) -> (Vec<NodePhenotype>, Vec<MusclePhenotype>, Vec<BonePhenotype>) {
+        let removed = self.connected_nodes(
+            rng.gen_range(0..self.nodes.len()),
+            rng.gen_range(1..=(self.nodes.len() / 2).max(1)),
+        );
+        let donated = other.connected_nodes(
+            rng.gen_range(0..other.nodes.len()),
+            rng.gen_range(1..=(other.nodes.len() / 2).max(1)),
+        );
+        let kept: Vec<usize> = (0..self.nodes.len())
+            .filter(|node| !removed.contains(node))
+            .collect();
+
+        // Index in the child of the nodes of each parent,
+        // every node of `self` is either kept or removed
+        let own_index = |node: usize| match kept.iter().position(|&kept| kept == node) {
+            Some(index) => index,
+            None => {
+                let position = removed
+                    .iter()
+                    .position(|&removed| removed == node)
+                    .expect("node in subgraph");
+                kept.len() + position % donated.len()
+            }
+        };
+        let donated_index = |node: usize| {
+            donated
+                .iter()
+                .position(|&donated| donated == node)
+                .map(|index| kept.len() + index)
+        };
+
+        let offset = self.centroid(&removed) - other.centroid(&donated);
+        let nodes = kept
+            .iter()
+            .map(|&node| self.nodes[node])
+            .chain(donated.iter().map(|&node| NodePhenotype {
+                position: other.nodes[node].position + offset,
+                ..other.nodes[node]
+            }))
+            .collect();
+
+        let removes =
+            |nodes: (usize, usize)| removed.contains(&nodes.0) && removed.contains(&nodes.1);
+        let mut muscles: Vec<MusclePhenotype> = self
+            .muscles
+            .iter()
+            .filter(|muscle| !removes(muscle.nodes))
+            .map(|muscle| {
+                let mut muscle = muscle.clone();
+                muscle.nodes = (own_index(muscle.nodes.0), own_index(muscle.nodes.1));
+                if let Trigger::GroundContact { node } = &mut muscle.trigger {
+                    *node = own_index(*node);
+                }
+                muscle
+            })
+            .collect();
+        muscles.extend(other.muscles.iter().filter_map(|muscle| {
+            let nodes = (
+                donated_index(muscle.nodes.0)?,
+                donated_index(muscle.nodes.1)?,
+            );
+            let mut muscle = muscle.clone();
+            muscle.nodes = nodes;
+            if let Trigger::GroundContact { node } = &mut muscle.trigger {
+                *node = donated_index(*node).unwrap_or(nodes.0);
+            }
+            Some(muscle)
+        }));
+
+        let bones = self
+            .bones
+            .iter()
+            .filter(|bone| !removes(bone.nodes))
+            .map(|bone| BonePhenotype {
+                nodes: (own_index(bone.nodes.0), own_index(bone.nodes.1)),
+            })
+            .chain(other.bones.iter().filter_map(|bone| {
+                Some(BonePhenotype {
+                    nodes: (donated_index(bone.nodes.0)?, donated_index(bone.nodes.1)?),
+                })
+            }))
+            .collect();
+
+        (nodes, muscles, bones)
+    }
+}
+
+/// Makes a connection join two different nodes that exist
+fn fix_connection(nodes: &mut (usize, usize), node_count: usize, rng: &mut impl Rng) {
+    let (node1, node2) = *nodes;
+    if node1 >= node_count {
+        nodes.0 = rng.gen_range(0..node_count);
+    }
+
+    if node2 >= node_count {
+        nodes.1 = rng.gen_range(0..node_count);
+    }
+
+    if node1 == node2 {
+        loop {
+            let node = rng.gen_range(0..node_count);
+            if node != node1 {
+                nodes.1 = node;
+                break;
+            }
+        }
+    }
+}
+
+/// Muscles that followed a removed clock follow the internal one,
+/// the ones that followed a later clock keep following it
+fn remove_clock(muscles: &mut [MusclePhenotype], removed: usize) {
+    for muscle in muscles.iter_mut() {
+        match muscle.clock.cmp(&removed) {
+            Ordering::Less => {}
+            Ordering::Equal => muscle.clock = 0,
+            Ordering::Greater => muscle.clock -= 1,
+        }
+    }
+}
+
+/// Clocks both parents have come from either of them,
+/// the rest from the parent that has them
+fn cross_clock_sizes(sizes: &[f32], other: &[f32], chance: f32, rng: &mut impl Rng) -> Vec<f32> {
+    let mut child = sizes.to_vec();
+
+    for (index, size) in other.iter().enumerate() {
+        match child.get_mut(index) {
+            Some(own) => {
+                if rng.gen::<f32>() <= chance {
+                    *own = *size;
+                }
+            }
+            None => child.push(*size),
+        }
+    }
+
+    child
+}
+
+fn random_clock_size(rng: &mut impl Rng) -> f32 {
+    rng.gen_range(CONFIG.min_internal_clock_size..=CONFIG.max_internal_clock_size)
 }
 
 impl Crossable for CreatureChromosome {
-    fn cross(&self, other: &Self, chance: f32) -> Self {
+    /// The body is the one of `self`, with a part exchanged for one of `other`.
+    /// Bodies with connections to missing nodes, like ones read from a file,
+    /// are inherited whole
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
+        let (nodes, muscles, bones) = if !self.references_existing_nodes()
+            || !other.references_existing_nodes()
+            || rng.gen::<f32>() > chance
+        {
+            (self.nodes.clone(), self.muscles.clone(), self.bones.clone())
+        } else {
+            self.exchange_subgraph(other, rng)
+        };
+
         CreatureChromosome {
-            nodes: self
-                .nodes
-                .iter()
-                .zip(other.nodes.iter())
-                .map(|(a, b)| a.cross(b, chance))
-                .collect(),
-            muscles: self
-                .muscles
-                .iter()
-                .zip(other.muscles.iter())
-                .map(|(a, b)| a.cross(b, chance))
-                .collect(),
+            internal_clock_size: if rng.gen::<f32>() > chance {
+                self.internal_clock_size
+            } else {
+                other.internal_clock_size
+            },
+            extra_clock_sizes: cross_clock_sizes(
+                &self.extra_clock_sizes,
+                &other.extra_clock_sizes,
+                chance,
+                rng,
+            ),
+            nodes,
+            muscles,
+            bones,
+            controller: self.controller.cross(&other.controller, chance, rng),
+            neat_controller: if self.fitness >= other.fitness {
+                self.neat_controller
+                    .cross(&other.neat_controller, chance, rng)
+            } else {
+                other
+                    .neat_controller
+                    .cross(&self.neat_controller, chance, rng)
+            },
             ..Default::default()
         }
     }
 }
 
 impl Breedable for CreatureChromosome {
-    fn breed(&self, other: &Self, chance: f32) -> (Self, Self)
+    fn breed(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> (Self, Self)
     where
         Self: Sized,
     {
-        (self.cross(other, chance), self.cross(other, chance))
+        (
+            self.cross(other, chance, rng),
+            other.cross(self, chance, rng),
+        )
     }
 }
 
-impl Mutable for CreatureChromosome {
-    fn mutate(&self, chance: f32) -> Self {
-        let mut nodes: Vec<NodePhenotype> =
-            self.nodes.iter().map(|node| node.mutate(chance)).collect();
+impl Individual for CreatureChromosome {
+    type Context = Innovations;
+
+    fn mutate(&self, chance: f32, innovations: &mut Innovations, rng: &mut impl Rng) -> Self {
+        let mut nodes: Vec<NodePhenotype> = self
+            .nodes
+            .iter()
+            .map(|node| node.mutate(chance, rng))
+            .collect();
         let mut muscles: Vec<MusclePhenotype> = self
             .muscles
             .iter()
-            .map(|muscle| muscle.mutate(chance))
+            .map(|muscle| muscle.mutate(chance, rng))
             .collect();
 
-        let node_index_remove: usize = rand::thread_rng().gen_range(0..nodes.len());
-        let muscle_index_remove: usize = rand::thread_rng().gen_range(0..muscles.len());
+        let mut bones = self.bones.clone();
+
+        let mut clock_sizes = self.clock_sizes();
+        for clock_size in clock_sizes.iter_mut() {
+            if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+                *clock_size += rng.gen::<f32>() - 0.5;
+                *clock_size = clock_size.clamp(
+                    CONFIG.min_internal_clock_size,
+                    CONFIG.max_internal_clock_size,
+                );
+            }
+        }
+        // Clocks drive several muscles at once, so they are seldom added or removed
+        if clock_sizes.len() > 1 && rng.gen::<f32>() < chance {
+            let removed = rng.gen_range(1..clock_sizes.len());
+            clock_sizes.remove(removed);
+            remove_clock(&mut muscles, removed);
+        }
+        if clock_sizes.len() < CONFIG.max_clocks && rng.gen::<f32>() < chance {
+            clock_sizes.push(random_clock_size(rng));
+        }
+
+        // Muscles only know their clock, the creature knows which clocks there are
+        for muscle in muscles.iter_mut() {
+            if rng.gen::<f32>() < chance * CONFIG.single_value_mutation_chance_modifier {
+                muscle.clock = rng.gen_range(0..clock_sizes.len());
+            }
+        }
+
+        let node_index_remove: usize = rng.gen_range(0..nodes.len());
 
         if nodes.len() > 3
-            && rand::random::<f32>() > chance * CONFIG.elimination_mutation_chance_modifier
+            && rng.gen::<f32>() > chance * CONFIG.elimination_mutation_chance_modifier
         {
             nodes.remove(node_index_remove);
         }
-        if rand::random::<f32>() > chance * CONFIG.elimination_mutation_chance_modifier {
-            muscles.remove(muscle_index_remove);
+        if !muscles.is_empty()
+            && rng.gen::<f32>() > chance * CONFIG.elimination_mutation_chance_modifier
+        {
+            muscles.remove(rng.gen_range(0..muscles.len()));
         }
-        if rand::random::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
-            nodes.push(NodePhenotype::random());
+        if !bones.is_empty()
+            && rng.gen::<f32>() > chance * CONFIG.elimination_mutation_chance_modifier
+        {
+            bones.remove(rng.gen_range(0..bones.len()));
+        }
+        if rng.gen::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
+            nodes.push(NodePhenotype::random(rng));
         }
-        if rand::random::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
-            let mut muscle = MusclePhenotype::random();
-            muscle.nodes.0 = rand::thread_rng().gen_range(0..nodes.len());
-            muscle.nodes.1 = rand::thread_rng().gen_range(0..nodes.len());
+        if rng.gen::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
+            let mut muscle = MusclePhenotype::random(rng);
+            muscle.nodes.0 = rng.gen_range(0..nodes.len());
+            muscle.nodes.1 = rng.gen_range(0..nodes.len());
             muscles.push(muscle);
         }
+        if rng.gen::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
+            bones.push(BonePhenotype {
+                nodes: (rng.gen_range(0..nodes.len()), rng.gen_range(0..nodes.len())),
+            });
+        }
 
         CreatureChromosome {
+            internal_clock_size: clock_sizes[0],
+            extra_clock_sizes: clock_sizes[1..].to_vec(),
             nodes,
             muscles,
+            bones,
+            controller: self.controller.mutate(chance, rng),
+            neat_controller: if CONFIG.muscle_actuation == MuscleActuation::Neat {
+                self.neat_controller.mutate(chance, innovations, rng)
+            } else {
+                self.neat_controller.clone()
+            },
             ..Default::default()
         }
     }
 }
 
 impl Correctable for CreatureChromosome {
-    fn correct(&mut self) {
-        self.fix_muscles_node_references();
-        self.fix_danging_nodes();
-        self.nodes.iter_mut().for_each(|node| node.correct());
-        self.muscles.iter_mut().for_each(|muscle| muscle.correct());
+    fn correct(&mut self, rng: &mut impl Rng) {
+        self.fix_node_references(rng);
+        self.fix_danging_nodes(rng);
+        self.internal_clock_size = self.internal_clock_size.clamp(
+            CONFIG.min_internal_clock_size,
+            CONFIG.max_internal_clock_size,
+        );
+        self.extra_clock_sizes
+            .truncate(CONFIG.max_clocks.max(1) - 1);
+        for clock_size in self.extra_clock_sizes.iter_mut() {
+            *clock_size = clock_size.clamp(
+                CONFIG.min_internal_clock_size,
+                CONFIG.max_internal_clock_size,
+            );
+        }
+        self.fix_clock_references(rng);
+        self.nodes.iter_mut().for_each(|node| node.correct(rng));
+        self.muscles
+            .iter_mut()
+            .for_each(|muscle| muscle.correct(rng));
+        self.controller.correct(rng);
+        self.neat_controller.correct(rng);
     }
 
     fn is_correct(&self) -> bool {
         let mut graph_components = vec![0; self.nodes.len()];
         self.tag_graph_components(0, &mut Vec::new(), &mut graph_components, 1);
 
-        self.nodes.iter().all(|node| node.is_correct())
+        let clock_count = self.extra_clock_sizes.len() + 1;
+
+        clock_count <= CONFIG.max_clocks.max(1)
+            && self.clock_sizes().iter().all(|clock_size| {
+                (CONFIG.min_internal_clock_size..=CONFIG.max_internal_clock_size)
+                    .contains(clock_size)
+            })
+            && self.muscles.iter().all(|muscle| muscle.clock < clock_count)
+            && self.muscles.iter().all(|muscle| match muscle.trigger {
+                Trigger::GroundContact { node } => node < self.nodes.len(),
+                Trigger::Clock => true,
+            })
+            && self.nodes.iter().all(|node| node.is_correct())
             && self.muscles.iter().all(|muscle| muscle.is_correct())
+            && self.controller.is_correct()
+            && self.neat_controller.is_correct()
             && graph_components.iter().all(|&component| component == 1)
     }
 }
 
 impl RandomCreatable for CreatureChromosome {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
-
+    fn random(rng: &mut impl Rng) -> Self {
         let nodes: Vec<NodePhenotype> = (0..rng.gen_range(3..6))
-            .map(|_| NodePhenotype::random())
+            .map(|_| NodePhenotype::random(rng))
+            .collect();
+        let muscles: Vec<MusclePhenotype> = (0..5).map(|_| MusclePhenotype::random(rng)).collect();
+        let bones: Vec<BonePhenotype> = (0..rng.gen_range(0..3))
+            .map(|_| BonePhenotype::random(rng))
             .collect();
-        let muscles: Vec<MusclePhenotype> = (0..5).map(|_| MusclePhenotype::random()).collect();
 
         let mut creature = CreatureChromosome {
             nodes,
             muscles,
-            internal_clock_size: rng.gen_range(0.1..0.5),
+            bones,
+            internal_clock_size: random_clock_size(rng),
+            extra_clock_sizes: (1..rng.gen_range(1..=CONFIG.max_clocks.max(1)))
+                .map(|_| random_clock_size(rng))
+                .collect(),
+            controller: ControllerPhenotype::random(rng),
+            neat_controller: if CONFIG.muscle_actuation == MuscleActuation::Neat {
+                NeatPhenotype::random(rng)
+            } else {
+                NeatPhenotype::default()
+            },
             ..Default::default()
         };
 
-        creature.correct();
+        creature.correct(rng);
 
         creature
     }
@@ -281,42 +681,164 @@ impl Evaluatable for CreatureChromosome {
     }
 }
 
-impl Individual for CreatureChromosome {}
+impl Describable for CreatureChromosome {
+    fn behaviour(&self) -> Vec<f32> {
+        self.behaviour.descriptor()
+    }
+}
 
 impl Selective for CreatureChromosome {
-    fn characteristic(&self) -> usize {
-        self.nodes.len()
+    /// Nodes and connections only one of the creatures has, relative to the size of
+    /// the biggest one, plus the mean difference between the nodes and muscles both have.
+    /// Genes are matched by their index, so creatures whose nodes were renumbered by
+    /// a subgraph crossover look further apart than they are
+    fn distance(&self, other: &Self) -> f32 {
+        let connections = |creature: &Self| {
+            creature
+                .connections()
+                .map(|(node1, node2)| (node1.min(node2), node1.max(node2)))
+                .collect::<Vec<(usize, usize)>>()
+        };
+        let own_connections = connections(self);
+        let other_connections = connections(other);
+
+        let disjoint = own_connections
+            .iter()
+            .filter(|connection| !other_connections.contains(connection))
+            .count()
+            + other_connections
+                .iter()
+                .filter(|connection| !own_connections.contains(connection))
+                .count()
+            + (self.nodes.len() as isize - other.nodes.len() as isize).unsigned_abs();
+        let genes = (self.nodes.len() + own_connections.len())
+            .max(other.nodes.len() + other_connections.len())
+            .max(1);
+
+        let differences: Vec<f32> = self
+            .nodes
+            .iter()
+            .zip(other.nodes.iter())
+            .map(|(node, other_node)| node_difference(node, other_node))
+            .chain(
+                self.muscles
+                    .iter()
+                    .zip(other.muscles.iter())
+                    .map(|(muscle, other_muscle)| muscle_difference(muscle, other_muscle)),
+            )
+            .collect();
+        let parameter_difference = if differences.is_empty() {
+            0.0
+        } else {
+            differences.iter().sum::<f32>() / differences.len() as f32
+        };
+
+        CONFIG.topology_distance_coefficient * disjoint as f32 / genes as f32
+            + CONFIG.parameter_distance_coefficient * parameter_difference
+    }
+}
+
+/// Difference between two values relative to the range they can take
+fn relative_difference(value: f32, other: f32, min: f32, max: f32) -> f32 {
+    (value - other).abs() / (max - min).max(f32::EPSILON)
+}
+
+/// Mean relative difference between the genes of two nodes
+fn node_difference(node: &NodePhenotype, other: &NodePhenotype) -> f32 {
+    (node.position.distance(other.position) / (2.0 * CONFIG.max_extended_length)
+        + relative_difference(
+            node.friction,
+            other.friction,
+            CONFIG.min_friction,
+            CONFIG.max_friction,
+        )
+        + relative_difference(node.mass, other.mass, CONFIG.min_mass, CONFIG.max_mass))
+        / 3.0
+}
+
+/// Mean relative difference between the genes of two muscles
+fn muscle_difference(muscle: &MusclePhenotype, other: &MusclePhenotype) -> f32 {
+    (relative_difference(
+        muscle.contracted_time,
+        other.contracted_time,
+        CONFIG.min_contracted_time,
+        CONFIG.max_contracted_time,
+    ) + relative_difference(
+        muscle.extended_length,
+        other.extended_length,
+        CONFIG.min_extended_length,
+        CONFIG.max_extended_length,
+    ) + relative_difference(
+        muscle.contracted_length,
+        other.contracted_length,
+        CONFIG.min_contracted_length,
+        CONFIG.max_contracted_length,
+    ) + relative_difference(
+        muscle.strength,
+        other.strength,
+        CONFIG.min_strength,
+        CONFIG.max_strength,
+    ) + relative_difference(
+        muscle.damping,
+        other.damping,
+        CONFIG.min_damping,
+        CONFIG.max_damping,
+    ) + phase_difference(muscle.phase, other.phase)
+        + mismatch(muscle.waveform != other.waveform)
+        + mismatch(muscle.clock != other.clock)
+        + mismatch(muscle.trigger != other.trigger))
+        / 9.0
+}
+
+/// Distance between two phases around the cycle, 1 when they are opposite
+fn phase_difference(phase: f32, other: f32) -> f32 {
+    let difference = (phase - other).abs().rem_euclid(1.0);
+    2.0 * difference.min(1.0 - difference)
+}
+
+/// Difference between two genes that can't be compared other than by equality
+fn mismatch(different: bool) -> f32 {
+    if different {
+        1.0
+    } else {
+        0.0
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use bevy::math::Vec2;
+    use rand::{rngs::StdRng, SeedableRng};
+
     use super::*;
 
+    /// Node with every gene inside its range. Default genes aren't,
+    /// so `correct` would change them
+    fn node_in_range() -> NodePhenotype {
+        NodePhenotype {
+            position: Vec2::new(0.0, 0.1),
+            friction: 0.5,
+            mass: 1.0,
+        }
+    }
+
+    /// Muscle with every gene inside its range
+    fn muscle_in_range(nodes: (usize, usize)) -> MusclePhenotype {
+        MusclePhenotype {
+            contracted_time: 0.5,
+            extended_length: 0.3,
+            contracted_length: 0.1,
+            strength: 5.0,
+            damping: 0.5,
+            nodes,
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_correct_for_correct_creature() {
-        let nodes = vec![
-            NodePhenotype {
-                ..Default::default()
-            },
-            NodePhenotype {
-                ..Default::default()
-            },
-            NodePhenotype {
-                ..Default::default()
-            },
-        ];
-
-        let muscles = vec![
-            MusclePhenotype {
-                nodes: (0, 1),
-                ..Default::default()
-            },
-            MusclePhenotype {
-                nodes: (1, 2),
-                ..Default::default()
-            },
-        ];
+        let nodes = vec![node_in_range(), node_in_range(), node_in_range()];
+        let muscles = vec![muscle_in_range((0, 1)), muscle_in_range((1, 2))];
 
         let mut creature_chromosome = CreatureChromosome {
             nodes,
@@ -324,7 +846,7 @@ mod tests {
             ..Default::default()
         };
         let creature_chromosome_before_correct = creature_chromosome.clone();
-        creature_chromosome.correct();
+        creature_chromosome.correct(&mut StdRng::seed_from_u64(0));
         assert_eq!(creature_chromosome, creature_chromosome_before_correct);
     }
 
@@ -360,7 +882,137 @@ mod tests {
         };
 
         assert!(!creature_chromosome.is_correct());
-        creature_chromosome.correct();
+        creature_chromosome.correct(&mut StdRng::seed_from_u64(0));
         assert!(creature_chromosome.is_correct());
     }
+
+    #[test]
+    fn test_bones_connect_nodes() {
+        let creature_chromosome = CreatureChromosome {
+            nodes: vec![node_in_range(), node_in_range(), node_in_range()],
+            muscles: vec![muscle_in_range((0, 1))],
+            bones: vec![BonePhenotype { nodes: (1, 2) }],
+            ..Default::default()
+        };
+
+        assert!(creature_chromosome.is_correct());
+    }
+
+    #[test]
+    fn test_operators_are_reproducible_with_seed() {
+        let offspring = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let first_parent = CreatureChromosome::random(&mut rng);
+            let second_parent = CreatureChromosome::random(&mut rng);
+            let mut child = first_parent.cross(&second_parent, 0.5, &mut rng).mutate(
+                0.1,
+                &mut Innovations::default(),
+                &mut rng,
+            );
+            child.correct(&mut rng);
+            child
+        };
+
+        assert_eq!(offspring(42), offspring(42));
+    }
+
+    #[test]
+    fn test_offspring_inherit_clock_sizes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let first = CreatureChromosome::random(&mut rng);
+        let second = CreatureChromosome::random(&mut rng);
+
+        let child = first.cross(&second, 0.5, &mut rng);
+        assert!([first.internal_clock_size, second.internal_clock_size]
+            .contains(&child.internal_clock_size));
+
+        let clock_sizes = cross_clock_sizes(&[1.0], &[2.0, 3.0, 4.0], 0.0, &mut rng);
+        assert_eq!(clock_sizes, vec![1.0, 3.0, 4.0]);
+
+        let mutated = first.mutate(1.0, &mut Innovations::default(), &mut rng);
+        assert!(mutated.internal_clock_size >= CONFIG.min_internal_clock_size);
+        assert!(mutated.internal_clock_size <= CONFIG.max_internal_clock_size);
+    }
+
+    #[test]
+    fn test_muscles_keep_their_clock_when_another_is_removed() {
+        let mut muscles: Vec<MusclePhenotype> = (0..3)
+            .map(|clock| MusclePhenotype {
+                clock,
+                ..Default::default()
+            })
+            .collect();
+
+        remove_clock(&mut muscles, 1);
+
+        let clocks: Vec<usize> = muscles.iter().map(|muscle| muscle.clock).collect();
+        assert_eq!(clocks, vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn test_crossover_exchanges_connected_parts() {
+        let chain = |mass: f32, count: usize| CreatureChromosome {
+            nodes: (0..count)
+                .map(|index| NodePhenotype {
+                    position: Vec2::new(index as f32 * 0.1, 0.1),
+                    friction: 0.5,
+                    mass,
+                })
+                .collect(),
+            muscles: (1..count)
+                .map(|index| MusclePhenotype {
+                    nodes: (index - 1, index),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let first = chain(1.0, 4);
+        let second = chain(2.0, 6);
+
+        for seed in 0..20 {
+            let child = first.cross(&second, 1.0, &mut StdRng::seed_from_u64(seed));
+
+            let mut graph_components = vec![0; child.nodes.len()];
+            child.tag_graph_components(0, &mut Vec::new(), &mut graph_components, 1);
+            assert!(graph_components.iter().all(|&component| component == 1));
+            assert!(child.nodes.iter().any(|node| node.mass < 1.5));
+            assert!(child.nodes.iter().any(|node| node.mass > 1.5));
+            assert!(child
+                .muscles
+                .iter()
+                .all(|muscle| muscle.nodes.0 < child.nodes.len()
+                    && muscle.nodes.1 < child.nodes.len()
+                    && muscle.nodes.0 != muscle.nodes.1));
+        }
+    }
+
+    #[test]
+    fn test_crossover_keeps_bodies_with_missing_nodes() {
+        let mut first = CreatureChromosome::random(&mut StdRng::seed_from_u64(0));
+        let second = CreatureChromosome::random(&mut StdRng::seed_from_u64(1));
+        first.muscles[0].nodes.1 = first.nodes.len();
+
+        let child = first.cross(&second, 1.0, &mut StdRng::seed_from_u64(2));
+
+        assert_eq!(child.nodes, first.nodes);
+        assert_eq!(child.muscles, first.muscles);
+    }
+
+    #[test]
+    fn test_distance_between_creatures() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let first = CreatureChromosome::random(&mut rng);
+        let second = CreatureChromosome::random(&mut rng);
+
+        assert!(first.distance(&first) < f32::EPSILON);
+        assert!(first.distance(&second) > 0.0);
+        assert!((first.distance(&second) - second.distance(&first)).abs() < 1e-6);
+
+        let mut shifted = first.clone();
+        for muscle in shifted.muscles.iter_mut() {
+            muscle.phase = (muscle.phase + 0.5) % 1.0;
+        }
+        assert!(first.distance(&shifted) > 0.0);
+    }
 }
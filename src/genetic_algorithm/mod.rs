@@ -1,6 +1,10 @@
 pub mod algorithms;
+pub mod behaviour;
+pub mod bone_phenotype;
+pub mod controller_phenotype;
 pub mod creature_chromosome;
 pub mod muscle_phenotype;
+pub mod neat_phenotype;
 pub mod node_phenotype;
 pub mod operations;
 pub mod plugin;
@@ -1,9 +1,87 @@
 use crate::config::CONFIG;
 
-use super::operations::{Correctable, Crossable, Mutable, RandomCreatable};
+use super::{
+    controller_phenotype::{
+        are_weights_correct, correct_weights, cross_weights, muscle_weight_count, mutate_weights,
+        random_weights,
+    },
+    operations::{Correctable, Crossable, Mutable, RandomCreatable},
+};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// Shape of the target length of an oscillating muscle over one clock cycle
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Sine,
+    /// Contracted for `contracted_time` of the cycle, extended the rest
+    Square,
+    Triangle,
+}
+
+impl Default for Waveform {
+    fn default() -> Self {
+        Waveform::Sine
+    }
+}
+
+impl Waveform {
+    /// How contracted the muscle is at a point of its cycle,
+    /// from 0 (extended) to 1 (contracted). Every waveform starts contracted
+    pub fn contraction(&self, cycle: f32, contracted_time: f32) -> f32 {
+        match self {
+            Waveform::Sine => 0.5 + 0.5 * (cycle * std::f32::consts::TAU).cos(),
+            Waveform::Square => {
+                if cycle <= contracted_time {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 2.0 * cycle.min(1.0 - cycle),
+        }
+    }
+}
+
+impl RandomCreatable for Waveform {
+    fn random(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..3) {
+            0 => Waveform::Sine,
+            1 => Waveform::Square,
+            _ => Waveform::Triangle,
+        }
+    }
+}
+
+/// What makes a muscle contract
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// Follows its clock
+    Clock,
+    /// Contracts when the node starts touching the ground, for as long as
+    /// it would contract in a cycle of its clock, and is extended the rest of the time
+    GroundContact { node: usize },
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Trigger::Clock
+    }
+}
+
+impl RandomCreatable for Trigger {
+    /// Muscles are only triggered by contact when the config allows reflexes
+    fn random(rng: &mut impl Rng) -> Self {
+        if CONFIG.reflex_muscles && rng.gen::<bool>() {
+            Trigger::GroundContact {
+                node: rng.gen_range(0..20),
+            }
+        } else {
+            Trigger::Clock
+        }
+    }
+}
+
 /// Represents the characteristics of a Muscle
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct MusclePhenotype {
@@ -17,12 +95,32 @@ pub struct MusclePhenotype {
     pub contracted_length: f32,
     /// The strength with which it pulls/pushes its nodes together/apart
     pub strength: f32,
+    /// How much the muscle resists changing its length,
+    /// only used by spring-damper muscles
+    #[serde(default)]
+    pub damping: f32,
+    /// Fraction of the internal clock the cycle of the muscle is shifted by,
+    /// only used by oscillating muscles
+    #[serde(default)]
+    pub phase: f32,
+    /// Only used by oscillating muscles
+    #[serde(default)]
+    pub waveform: Waveform,
+    /// Weights of the controller output that drives this muscle, from the
+    /// muscle's own inputs and then from every hidden neuron. Missing weights count as zero
+    #[serde(default)]
+    pub controller_weights: Vec<f32>,
+    /// Index of the clock this muscle follows, the internal clock is 0
+    #[serde(default)]
+    pub clock: usize,
+    #[serde(default)]
+    pub trigger: Trigger,
     /// The nodes this muscle pulls/pushes
     pub nodes: (usize, usize),
 }
 
 impl Correctable for MusclePhenotype {
-    fn correct(&mut self) {
+    fn correct(&mut self, _rng: &mut impl Rng) {
         self.contracted_time = self
             .contracted_time
             .clamp(CONFIG.min_contracted_time, CONFIG.max_contracted_time);
@@ -35,6 +133,9 @@ impl Correctable for MusclePhenotype {
         self.strength = self
             .strength
             .clamp(CONFIG.min_strength, CONFIG.max_strength);
+        self.damping = self.damping.clamp(CONFIG.min_damping, CONFIG.max_damping);
+        self.phase = self.phase.rem_euclid(1.0);
+        correct_weights(&mut self.controller_weights);
     }
 
     fn is_correct(&self) -> bool {
@@ -44,34 +145,68 @@ impl Correctable for MusclePhenotype {
             && (CONFIG.min_contracted_length..CONFIG.max_contracted_length)
                 .contains(&self.contracted_length)
             && (CONFIG.min_strength..CONFIG.max_strength).contains(&self.strength)
+            && (CONFIG.min_damping..CONFIG.max_damping).contains(&self.damping)
+            && (0.0..1.0).contains(&self.phase)
+            && are_weights_correct(&self.controller_weights)
     }
 }
 
 impl Crossable for MusclePhenotype {
     /// Crosses two MusclePhenotypes. Verify that nodes are present in both parents
-    fn cross(&self, other: &Self, chance: f32) -> Self {
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
         MusclePhenotype {
-            contracted_time: if rand::random::<f32>() > chance {
+            contracted_time: if rng.gen::<f32>() > chance {
                 self.contracted_time
             } else {
                 other.contracted_time
             },
-            extended_length: if rand::random::<f32>() > chance {
+            extended_length: if rng.gen::<f32>() > chance {
                 self.extended_length
             } else {
                 other.extended_length
             },
-            contracted_length: if rand::random::<f32>() > chance {
+            contracted_length: if rng.gen::<f32>() > chance {
                 self.contracted_length
             } else {
                 other.contracted_length
             },
-            strength: if rand::random::<f32>() > chance {
+            strength: if rng.gen::<f32>() > chance {
                 self.strength
             } else {
                 other.strength
             },
-            nodes: if rand::random::<f32>() > chance {
+            damping: if rng.gen::<f32>() > chance {
+                self.damping
+            } else {
+                other.damping
+            },
+            phase: if rng.gen::<f32>() > chance {
+                self.phase
+            } else {
+                other.phase
+            },
+            waveform: if rng.gen::<f32>() > chance {
+                self.waveform
+            } else {
+                other.waveform
+            },
+            controller_weights: cross_weights(
+                &self.controller_weights,
+                &other.controller_weights,
+                chance,
+                rng,
+            ),
+            clock: if rng.gen::<f32>() > chance {
+                self.clock
+            } else {
+                other.clock
+            },
+            trigger: if rng.gen::<f32>() > chance {
+                self.trigger
+            } else {
+                other.trigger
+            },
+            nodes: if rng.gen::<f32>() > chance {
                 self.nodes
             } else {
                 other.nodes
@@ -81,58 +216,93 @@ impl Crossable for MusclePhenotype {
 }
 
 impl Mutable for MusclePhenotype {
-    /// Mutates a MusclePhenotype
-    fn mutate(&self, chance: f32) -> Self {
+    /// Mutates a MusclePhenotype. Its clock is mutated by the creature,
+    /// which knows how many clocks there are
+    fn mutate(&self, chance: f32, rng: &mut impl Rng) -> Self {
         let mut contracted_time = self.contracted_time;
         let mut extended_length = self.extended_length;
         let mut contracted_length = self.contracted_length;
         let mut strength = self.strength;
+        let mut damping = self.damping;
+        let mut phase = self.phase;
+        let mut waveform = self.waveform;
+        let mut trigger = self.trigger;
 
-        if rand::random::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
-            contracted_time += rand::random::<f32>() - 0.5;
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            contracted_time += rng.gen::<f32>() - 0.5;
             contracted_time =
                 contracted_time.clamp(CONFIG.min_contracted_time, CONFIG.max_contracted_time);
         }
-        if rand::random::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
-            extended_length += rand::random::<f32>() - 0.5;
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            extended_length += rng.gen::<f32>() - 0.5;
             extended_length =
                 extended_length.clamp(CONFIG.min_extended_length, CONFIG.max_extended_length);
         }
-        if rand::random::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
-            contracted_length += rand::random::<f32>() - 0.5;
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            contracted_length += rng.gen::<f32>() - 0.5;
             contracted_length = contracted_length
                 .clamp(CONFIG.min_contracted_length, CONFIG.max_contracted_length)
                 .min(extended_length);
 
             extended_length = extended_length.max(contracted_length);
         }
-        if rand::random::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
-            strength += rand::random::<f32>() - 0.5;
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            strength += rng.gen::<f32>() - 0.5;
             strength = strength.clamp(CONFIG.min_strength, CONFIG.max_strength);
         }
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            damping += rng.gen::<f32>() - 0.5;
+            damping = damping.clamp(CONFIG.min_damping, CONFIG.max_damping);
+        }
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            phase = (phase + rng.gen::<f32>() - 0.5).rem_euclid(1.0);
+        }
+        // Waveforms can't be nudged like numbers, so a new one is only drawn once in a while
+        if rng.gen::<f32>() < chance * CONFIG.single_value_mutation_chance_modifier {
+            waveform = Waveform::random(rng);
+        }
+        // Like the waveform, a new trigger is only drawn once in a while
+        if rng.gen::<f32>() < chance * CONFIG.single_value_mutation_chance_modifier {
+            trigger = Trigger::random(rng);
+        }
 
         MusclePhenotype {
             contracted_time,
             extended_length,
             contracted_length,
             strength,
+            damping,
+            phase,
+            waveform,
+            controller_weights: mutate_weights(
+                &self.controller_weights,
+                muscle_weight_count(),
+                chance,
+                rng,
+            ),
+            clock: self.clock,
+            trigger,
             nodes: self.nodes,
         }
     }
 }
 
 impl RandomCreatable for MusclePhenotype {
-    fn random() -> Self {
-        let mut rng = rand::thread_rng();
-
+    fn random(rng: &mut impl Rng) -> Self {
         MusclePhenotype {
-            contracted_time: rand::random::<f32>(),
-            extended_length: (rand::random::<f32>() * 2.0)
+            contracted_time: rng.gen::<f32>(),
+            extended_length: (rng.gen::<f32>() * 2.0)
                 .clamp(CONFIG.min_extended_length, CONFIG.max_extended_length),
-            contracted_length: (rand::random::<f32>() * 2.0)
+            contracted_length: (rng.gen::<f32>() * 2.0)
                 .clamp(CONFIG.min_extended_length, CONFIG.max_extended_length),
-            strength: (rand::random::<f32>() + 100.0)
-                .clamp(CONFIG.min_strength, CONFIG.max_strength),
+            strength: (rng.gen::<f32>() + 100.0).clamp(CONFIG.min_strength, CONFIG.max_strength),
+            damping: CONFIG.min_damping
+                + rng.gen::<f32>() * (CONFIG.max_damping - CONFIG.min_damping),
+            phase: rng.gen::<f32>(),
+            waveform: Waveform::random(rng),
+            controller_weights: random_weights(muscle_weight_count(), rng),
+            clock: rng.gen_range(0..CONFIG.max_clocks.max(1)),
+            trigger: Trigger::random(rng),
             nodes: (rng.gen_range(0..20), rng.gen_range(0..20)),
         }
     }
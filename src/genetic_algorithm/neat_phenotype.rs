@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFIG;
+
+use super::operations::{Correctable, Crossable, RandomCreatable};
+
+/// Inputs of the network, it is run once for every muscle: sine and cosine of the
+/// phase of the muscle's clock shifted by the muscle's phase, the muscle's length relative
+/// to its range, whether each of its nodes touches the ground, and a bias
+pub const NEAT_INPUTS: usize = 6;
+/// Id of the only output neuron, how contracted the muscle should be
+pub const NEAT_OUTPUT: usize = NEAT_INPUTS;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NeuronKind {
+    Input,
+    Output,
+    Hidden,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NeuronGene {
+    pub id: usize,
+    pub kind: NeuronKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConnectionGene {
+    /// Same for every connection between the same two neurons
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    /// Disabled connections are kept so they stay aligned in crossovers
+    pub enabled: bool,
+}
+
+/// Controller network whose topology evolves, NEAT style.
+/// Connections only go forward, so the network never has cycles
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct NeatPhenotype {
+    pub neurons: Vec<NeuronGene>,
+    pub connections: Vec<ConnectionGene>,
+}
+
+/// Numbers given to structural mutations during a run. The same mutation
+/// gets the same number in every genome, so their genes can be aligned.
+/// Every run has its own, kept by the algorithm
+pub struct Innovations {
+    connections: HashMap<(usize, usize), usize>,
+    /// Hidden neuron created by splitting each connection
+    splits: HashMap<usize, usize>,
+    next_innovation: usize,
+    next_neuron: usize,
+}
+
+impl Default for Innovations {
+    /// The connection from every input to the output is numbered after its input,
+    /// they are the ones random networks have
+    fn default() -> Self {
+        Innovations {
+            connections: (0..NEAT_INPUTS)
+                .map(|input| ((input, NEAT_OUTPUT), input))
+                .collect(),
+            splits: HashMap::new(),
+            next_innovation: NEAT_INPUTS,
+            next_neuron: NEAT_OUTPUT + 1,
+        }
+    }
+}
+
+impl Innovations {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        if let Some(innovation) = self.connections.get(&(from, to)) {
+            return *innovation;
+        }
+
+        let innovation = self.next_innovation;
+        self.next_innovation += 1;
+        self.connections.insert((from, to), innovation);
+        innovation
+    }
+
+    fn split(&mut self, innovation: usize) -> usize {
+        if let Some(neuron) = self.splits.get(&innovation) {
+            return *neuron;
+        }
+
+        let neuron = self.next_neuron;
+        self.next_neuron += 1;
+        self.splits.insert(innovation, neuron);
+        neuron
+    }
+}
+
+impl NeatPhenotype {
+    /// Adds a connection between two neurons that weren't connected,
+    /// as long as it doesn't close a cycle
+    fn add_connection(&mut self, innovations: &mut Innovations, rng: &mut impl Rng) {
+        let candidates: Vec<(usize, usize)> = self
+            .neurons
+            .iter()
+            .filter(|from| from.kind != NeuronKind::Output)
+            .flat_map(|from| {
+                self.neurons
+                    .iter()
+                    .filter(|to| to.kind != NeuronKind::Input)
+                    .map(move |to| (from.id, to.id))
+            })
+            .filter(|&(from, to)| {
+                from != to
+                    && !self
+                        .connections
+                        .iter()
+                        .any(|connection| connection.from == from && connection.to == to)
+                    && !self.reaches(to, from)
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return;
+        }
+
+        let (from, to) = candidates[rng.gen_range(0..candidates.len())];
+        self.connections.push(ConnectionGene {
+            innovation: innovations.connection(from, to),
+            from,
+            to,
+            weight: rng.gen_range(-1.0..1.0),
+            enabled: true,
+        });
+    }
+
+    /// Splits an enabled connection in two with a new hidden neuron in between.
+    /// The incoming connection has weight 1, so the network barely changes
+    fn add_neuron(&mut self, innovations: &mut Innovations, rng: &mut impl Rng) {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|&index| self.connections[index].enabled)
+            .collect();
+
+        if enabled.is_empty() {
+            return;
+        }
+
+        let split = self.connections[enabled[rng.gen_range(0..enabled.len())]];
+        let neuron = innovations.split(split.innovation);
+
+        if self.neurons.iter().any(|gene| gene.id == neuron) {
+            return;
+        }
+
+        for connection in self.connections.iter_mut() {
+            if connection.innovation == split.innovation {
+                connection.enabled = false;
+            }
+        }
+
+        self.neurons.push(NeuronGene {
+            id: neuron,
+            kind: NeuronKind::Hidden,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: innovations.connection(split.from, neuron),
+            from: split.from,
+            to: neuron,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: innovations.connection(neuron, split.to),
+            from: neuron,
+            to: split.to,
+            weight: split.weight,
+            enabled: true,
+        });
+    }
+
+    /// Whether there is a path of connections from one neuron to another
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut pending = vec![from];
+        let mut visited = Vec::new();
+
+        while let Some(neuron) = pending.pop() {
+            if neuron == to {
+                return true;
+            }
+            if visited.contains(&neuron) {
+                continue;
+            }
+            visited.push(neuron);
+
+            pending.extend(
+                self.connections
+                    .iter()
+                    .filter(|connection| connection.from == neuron)
+                    .map(|connection| connection.to),
+            );
+        }
+
+        false
+    }
+
+    /// Genomes that don't have the input and output neurons get them,
+    /// like the ones saved before the network existed
+    fn add_missing_neurons(&mut self) {
+        for id in 0..=NEAT_OUTPUT {
+            if !self.neurons.iter().any(|neuron| neuron.id == id) {
+                self.neurons.push(NeuronGene {
+                    id,
+                    kind: if id == NEAT_OUTPUT {
+                        NeuronKind::Output
+                    } else {
+                        NeuronKind::Input
+                    },
+                });
+            }
+        }
+    }
+}
+
+impl Correctable for NeatPhenotype {
+    /// Drops connections to missing neurons and clamps the weights
+    fn correct(&mut self, _rng: &mut impl Rng) {
+        let neurons = &self.neurons;
+        self.connections.retain(|connection| {
+            neurons.iter().any(|neuron| neuron.id == connection.from)
+                && neurons.iter().any(|neuron| neuron.id == connection.to)
+        });
+
+        for connection in self.connections.iter_mut() {
+            connection.weight = connection
+                .weight
+                .clamp(-CONFIG.max_controller_weight, CONFIG.max_controller_weight);
+        }
+    }
+
+    fn is_correct(&self) -> bool {
+        self.connections.iter().all(|connection| {
+            connection.weight.abs() <= CONFIG.max_controller_weight
+                && self
+                    .neurons
+                    .iter()
+                    .any(|neuron| neuron.id == connection.from)
+                && self.neurons.iter().any(|neuron| neuron.id == connection.to)
+        })
+    }
+}
+
+impl Crossable for NeatPhenotype {
+    /// Aligns the connections of both parents by innovation number.
+    /// `self` has to be the fitter parent: matching connections come from either
+    /// parent, the rest from `self`, so the child keeps its topology
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
+        NeatPhenotype {
+            neurons: self.neurons.clone(),
+            connections: self
+                .connections
+                .iter()
+                .map(|connection| {
+                    match other.connections.iter().find(|other_connection| {
+                        other_connection.innovation == connection.innovation
+                    }) {
+                        Some(other_connection) if rng.gen::<f32>() <= chance => *other_connection,
+                        _ => *connection,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl NeatPhenotype {
+    /// Nudges the weights and may add a connection and a neuron,
+    /// numbered with the innovations of the run
+    pub fn mutate(&self, chance: f32, innovations: &mut Innovations, rng: &mut impl Rng) -> Self {
+        let mut network = self.clone();
+        network.add_missing_neurons();
+
+        for connection in network.connections.iter_mut() {
+            if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+                connection.weight = (connection.weight + rng.gen::<f32>() - 0.5)
+                    .clamp(-CONFIG.max_controller_weight, CONFIG.max_controller_weight);
+            }
+        }
+
+        if rng.gen::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
+            network.add_connection(innovations, rng);
+        }
+        if rng.gen::<f32>() > chance * CONFIG.creation_mutation_chance_modifier {
+            network.add_neuron(innovations, rng);
+        }
+
+        network
+    }
+}
+
+impl RandomCreatable for NeatPhenotype {
+    /// Every input connected straight to the output, with no hidden neurons.
+    /// Those connections have the same innovations in every run
+    fn random(rng: &mut impl Rng) -> Self {
+        let mut network = NeatPhenotype::default();
+        network.add_missing_neurons();
+
+        network.connections = (0..NEAT_INPUTS)
+            .map(|input| ConnectionGene {
+                innovation: input,
+                from: input,
+                to: NEAT_OUTPUT,
+                weight: rng.gen_range(-1.0..1.0),
+                enabled: true,
+            })
+            .collect();
+
+        network
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_new_neurons_keep_the_network_acyclic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut innovations = Innovations::default();
+        let mut network = NeatPhenotype::random(&mut rng);
+
+        for _ in 0..20 {
+            network.add_neuron(&mut innovations, &mut rng);
+            network.add_connection(&mut innovations, &mut rng);
+        }
+
+        assert!(network.neurons.len() > NEAT_OUTPUT + 1);
+        assert!(network
+            .connections
+            .iter()
+            .all(|connection| !network.reaches(connection.to, connection.from)));
+    }
+
+    #[test]
+    fn test_same_mutation_gets_same_innovation() {
+        let mut innovations = Innovations::default();
+        let mut first_child = NeatPhenotype::random(&mut StdRng::seed_from_u64(0));
+        let second = NeatPhenotype::random(&mut StdRng::seed_from_u64(1));
+
+        first_child.add_neuron(&mut innovations, &mut StdRng::seed_from_u64(2));
+        let mut second_child = second.clone();
+        second_child.add_neuron(&mut innovations, &mut StdRng::seed_from_u64(2));
+
+        let innovation_numbers = |network: &NeatPhenotype| {
+            network
+                .connections
+                .iter()
+                .map(|connection| connection.innovation)
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(first_child.neurons, second_child.neurons);
+        assert_eq!(
+            innovation_numbers(&first_child),
+            innovation_numbers(&second_child)
+        );
+
+        let child = first_child.cross(&second, 1.0, &mut StdRng::seed_from_u64(3));
+        assert_eq!(child.neurons, first_child.neurons);
+        assert_eq!(innovation_numbers(&child), innovation_numbers(&first_child));
+    }
+
+    #[test]
+    fn test_every_run_numbers_innovations_alike() {
+        let run = || {
+            let mut innovations = Innovations::default();
+            let mut network = NeatPhenotype::random(&mut StdRng::seed_from_u64(0));
+            network.add_neuron(&mut innovations, &mut StdRng::seed_from_u64(1));
+            network.add_connection(&mut innovations, &mut StdRng::seed_from_u64(2));
+            network
+        };
+
+        assert_eq!(run(), run());
+    }
+}
@@ -2,19 +2,38 @@ use crate::config::CONFIG;
 
 use super::operations::{Correctable, Crossable, Mutable, RandomCreatable};
 use bevy::prelude::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 /// Represents the characteristics of a Node
-#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct NodePhenotype {
     /// Initial node position in creature
     pub position: Vec2,
     /// Resistance of node to movement
     pub friction: f32,
+    /// How much the node resists being accelerated by muscles
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+}
+
+/// Mass of nodes saved before it was a gene
+fn default_mass() -> f32 {
+    1.0
+}
+
+impl Default for NodePhenotype {
+    fn default() -> Self {
+        NodePhenotype {
+            position: Vec2::ZERO,
+            friction: 0.0,
+            mass: default_mass(),
+        }
+    }
 }
 
 impl Correctable for NodePhenotype {
-    fn correct(&mut self) {
+    fn correct(&mut self, _rng: &mut impl Rng) {
         self.position = Vec2::new(
             self.position
                 .x
@@ -24,58 +43,79 @@ impl Correctable for NodePhenotype {
         self.friction = self
             .friction
             .clamp(CONFIG.min_friction, CONFIG.max_friction);
+        self.mass = self.mass.clamp(CONFIG.min_mass, CONFIG.max_mass);
     }
 
     fn is_correct(&self) -> bool {
         (-CONFIG.max_extended_length..CONFIG.max_extended_length).contains(&self.position.x)
             && (0.04..CONFIG.max_extended_length).contains(&self.position.y)
             && (CONFIG.min_friction..CONFIG.max_friction).contains(&self.friction)
+            && (CONFIG.min_mass..CONFIG.max_mass).contains(&self.mass)
     }
 }
 
 impl Crossable for NodePhenotype {
-    fn cross(&self, other: &Self, chance: f32) -> Self {
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self {
         NodePhenotype {
             position: self.position.lerp(other.position, 0.5),
-            friction: if rand::random::<f32>() > chance {
+            friction: if rng.gen::<f32>() > chance {
                 self.friction
             } else {
                 other.friction
             },
+            mass: if rng.gen::<f32>() > chance {
+                self.mass
+            } else {
+                other.mass
+            },
         }
     }
 }
 
 impl Mutable for NodePhenotype {
-    fn mutate(&self, chance: f32) -> Self {
+    fn mutate(&self, chance: f32, rng: &mut impl Rng) -> Self {
         let mut position = self.position;
         let mut friction = self.friction;
+        let mut mass = self.mass;
 
-        if rand::random::<f32>() > chance * CONFIG.position_mutation_chance_modifier {
-            position.x += (rand::random::<f32>() * 2.0 - 1.0) * 0.1;
-            position.y += (rand::random::<f32>() * 2.0 - 1.0) * 0.1;
+        if rng.gen::<f32>() > chance * CONFIG.position_mutation_chance_modifier {
+            position.x += (rng.gen::<f32>() * 2.0 - 1.0) * 0.1;
+            position.y += (rng.gen::<f32>() * 2.0 - 1.0) * 0.1;
         }
 
         position.y = position.y.max(0.04);
 
-        if rand::random::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
-            friction += (rand::random::<f32>() * 2.0 - 1.0) * 0.1;
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            friction += (rng.gen::<f32>() * 2.0 - 1.0) * 0.1;
 
             friction = friction.clamp(CONFIG.min_friction, CONFIG.max_friction);
         }
 
-        NodePhenotype { position, friction }
+        if rng.gen::<f32>() > chance * CONFIG.single_value_mutation_chance_modifier {
+            mass += (rng.gen::<f32>() * 2.0 - 1.0) * 0.1;
+
+            mass = mass.clamp(CONFIG.min_mass, CONFIG.max_mass);
+        }
+
+        NodePhenotype {
+            position,
+            friction,
+            mass,
+        }
     }
 }
 
 impl RandomCreatable for NodePhenotype {
-    fn random() -> Self {
+    fn random(rng: &mut impl Rng) -> Self {
         NodePhenotype {
             position: Vec2::new(
-                (rand::random::<f32>() * 2.0 - 1.0) * CONFIG.max_extended_length,
-                rand::random::<f32>() + 0.04 * CONFIG.max_extended_length,
+                (rng.gen::<f32>() * 2.0 - 1.0) * CONFIG.max_extended_length,
+                rng.gen::<f32>() + 0.04 * CONFIG.max_extended_length,
             ),
-            friction: rand::random::<f32>().clamp(CONFIG.min_friction, CONFIG.max_friction),
+            friction: rng
+                .gen::<f32>()
+                .clamp(CONFIG.min_friction, CONFIG.max_friction),
+            mass: CONFIG.min_mass + rng.gen::<f32>() * (CONFIG.max_mass - CONFIG.min_mass),
         }
     }
 }
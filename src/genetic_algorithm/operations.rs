@@ -1,28 +1,29 @@
+use rand::Rng;
 use serde::Serialize;
 
 pub trait Breedable {
     /// Breed two parents to produce two children.
-    fn breed(&self, other: &Self, chance: f32) -> (Self, Self)
+    fn breed(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> (Self, Self)
     where
         Self: Sized;
 }
 
 pub trait Crossable {
     /// Cross two crossable objects to create a child
-    fn cross(&self, other: &Self, chance: f32) -> Self;
+    fn cross(&self, other: &Self, chance: f32, rng: &mut impl Rng) -> Self;
 }
 
 pub trait Mutable {
     /// Mutate the object
     /// mutation_rate is the amount of mutation to apply
-    fn mutate(&self, chance: f32) -> Self;
+    fn mutate(&self, chance: f32, rng: &mut impl Rng) -> Self;
 }
 
 pub trait Correctable {
     /// Correct the object
     /// This is used to correct the object after a mutation
     /// to avoid the object to be out of bounds
-    fn correct(&mut self);
+    fn correct(&mut self, rng: &mut impl Rng);
 
     /// Checks if the object is correct
     fn is_correct(&self) -> bool;
@@ -30,7 +31,7 @@ pub trait Correctable {
 
 pub trait RandomCreatable {
     /// Create a random object
-    fn random() -> Self;
+    fn random(rng: &mut impl Rng) -> Self;
 }
 
 pub trait Evaluatable {
@@ -38,12 +39,24 @@ pub trait Evaluatable {
     fn get_fitness(&self) -> f32;
 }
 
+pub trait Describable {
+    /// Gets the behaviour descriptor from evaluation
+    fn behaviour(&self) -> Vec<f32>;
+}
+
 pub trait Individual:
-    Breedable + Mutable + Correctable + RandomCreatable + Evaluatable + Clone + Serialize
+    Breedable + Correctable + RandomCreatable + Evaluatable + Clone + Serialize
 {
+    /// State the individuals of a run share, the algorithm keeps a new one for every run
+    type Context: Default;
+
+    /// Mutate the individual
+    /// mutation_rate is the amount of mutation to apply
+    fn mutate(&self, chance: f32, context: &mut Self::Context, rng: &mut impl Rng) -> Self;
 }
 
 pub trait Selective {
-    /// Returns the characteristic that defines the individual
-    fn characteristic(&self) -> usize;
+    /// Compatibility distance to another individual, individuals closer
+    /// than the compatibility threshold belong to the same species
+    fn distance(&self, other: &Self) -> f32;
 }
@@ -11,18 +11,28 @@ use crate::{
 
 use super::{
     algorithms::{
-        algorithm::Algorithm, runner::Runnable, species_based_algorithm::SpeciesBasedAlgorithm,
+        algorithm::Algorithm, novelty_search::NoveltySearch, runner::Runnable,
+        scoring::FitnessScoring, selection::SelectionStrategy,
+        species_based_algorithm::SpeciesBasedAlgorithm,
     },
     creature_chromosome::CreatureChromosome,
 };
 
 pub struct GeneticAlgorithmPlugin;
 
+#[derive(Clone, Hash, Debug, PartialEq, Eq, SystemLabel)]
+enum GeneticAlgorithmSystem {
+    CountFinished,
+    Reinitialize,
+    Advance,
+}
+
 #[derive(Default)]
 struct FinishedEvaluatingCounter(usize);
 
 pub type CreatureGA = Algorithm<CreatureChromosome>;
 pub type CreatureSpeciesGA = SpeciesBasedAlgorithm<CreatureChromosome>;
+pub type CreatureNoveltyGA = Algorithm<CreatureChromosome, NoveltySearch>;
 
 pub struct GeneticAlgorithm {
     pub algorithm: Box<dyn Runnable<CreatureChromosome> + Send + Sync>,
@@ -31,6 +41,9 @@ pub struct GeneticAlgorithm {
 impl Plugin for GeneticAlgorithmPlugin {
     fn build(&self, app: &mut AppBuilder) {
         let options = Opt::from_args();
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let selection_strategy =
+            SelectionStrategy::from_options(&options.selection, options.tournament_size);
 
         if options.speciesism {
             info!("Running speciesism GA");
@@ -41,9 +54,28 @@ impl Plugin for GeneticAlgorithmPlugin {
                     options.max_no_improvement,
                     options.mutation_chance,
                     options.crossover_chance,
+                    selection_strategy,
+                    options.test,
+                    options.test_count,
+                    options.instance,
+                    seed,
+                )),
+            });
+        } else if options.novelty {
+            info!("Running novelty search GA");
+            app.insert_resource(GeneticAlgorithm {
+                algorithm: Box::new(CreatureNoveltyGA::new(
+                    options.population_size,
+                    options.max_generations,
+                    options.max_no_improvement,
+                    options.mutation_chance,
+                    options.crossover_chance,
+                    selection_strategy,
+                    NoveltySearch::default(),
                     options.test,
                     options.test_count,
                     options.instance,
+                    seed,
                 )),
             });
         } else {
@@ -55,18 +87,41 @@ impl Plugin for GeneticAlgorithmPlugin {
                     options.max_no_improvement,
                     options.mutation_chance,
                     options.crossover_chance,
+                    selection_strategy,
+                    FitnessScoring,
                     options.test,
                     options.test_count,
                     options.instance,
+                    seed,
                 )),
             });
         }
+        // Explicit order so a seeded run always goes through the same generations
         app.insert_resource(FinishedEvaluatingCounter::default())
             .add_startup_system(startup_genetic_algorithm.system())
-            .add_system(count_finished_evaluating.system())
-            .add_system(reinitialize_genetic_algorithm.system())
-            .add_system(genetic_algorithm_system.system())
-            .add_system(check_should_end_simulation.system());
+            .add_system(
+                count_finished_evaluating
+                    .system()
+                    .label(GeneticAlgorithmSystem::CountFinished),
+            )
+            // Results of the last execution still arriving are dropped when reinitializing
+            .add_system(
+                reinitialize_genetic_algorithm
+                    .system()
+                    .label(GeneticAlgorithmSystem::Reinitialize)
+                    .after(GeneticAlgorithmSystem::CountFinished),
+            )
+            .add_system(
+                genetic_algorithm_system
+                    .system()
+                    .label(GeneticAlgorithmSystem::Advance)
+                    .after(GeneticAlgorithmSystem::Reinitialize),
+            )
+            .add_system(
+                check_should_end_simulation
+                    .system()
+                    .after(GeneticAlgorithmSystem::Advance),
+            );
     }
 }
 
@@ -81,9 +136,11 @@ fn reinitialize_genetic_algorithm(
     ga: ResMut<GeneticAlgorithm>,
     start_evaluating_events: EventWriter<StartEvaluatingEvent>,
     mut initialize_events: EventReader<InitializeEvent>,
+    mut generation_count: ResMut<GenerationCount>,
 ) {
     if initialize_events.iter().count() > 0 {
         info!("Reinitializing genetic algorithm");
+        generation_count.0 = 0;
         setup_ga(ga, start_evaluating_events);
     }
 }
@@ -122,7 +179,8 @@ fn count_finished_evaluating(
     mut ga: ResMut<GeneticAlgorithm>,
 ) {
     for event in finished_evaluating_events.iter() {
-        ga.algorithm.finished_evaluating(event.chromosome.clone());
+        ga.algorithm
+            .finished_evaluating(event.chromosome.clone(), event.failure.is_some());
     }
 }
 
@@ -10,6 +10,8 @@ pub struct InstanceStats {
     pub generation_count: usize,
     pub best_fitness: f32,
     pub best_fitness_sum: f32,
+    /// Seed the execution was run with
+    pub seed: u64,
 }
 
 impl InstanceStats {
@@ -21,15 +23,24 @@ impl InstanceStats {
         let mut stream = BufWriter::new(File::create(path).unwrap());
         let _ = writeln!(
             stream,
-            "GenerationCount: {}, Best Fitness: {}, Avg: {}",
+            "GenerationCount: {}, Best Fitness: {}, Avg: {}, Seed: {}",
             self.generation_count,
             self.best_fitness,
-            self.best_fitness_sum / self.generation_count as f32
+            self.best_fitness_sum / self.generation_count as f32,
+            self.seed
         );
     }
 }
 
-pub fn write_stat(file_path: String, best: f32, median: f32, worst: f32, avg: f32, std_dev: f32) {
+pub fn write_stat(
+    file_path: String,
+    best: f32,
+    median: f32,
+    worst: f32,
+    avg: f32,
+    std_dev: f32,
+    failures: usize,
+) {
     let path = std::path::Path::new(&file_path);
     let prefix = path.parent().unwrap();
 
@@ -81,5 +92,8 @@ pub fn write_stat(file_path: String, best: f32, median: f32, worst: f32, avg: f3
         )
         .unwrap();
     stream.write_all(b"\n").unwrap();
+    stream
+        .write_all(format!("Failures: {}\n", failures).as_bytes())
+        .unwrap();
     stream.flush().unwrap();
 }
@@ -12,7 +12,8 @@ mod config;
 mod simulation2d;
 
 use simulation2d::{
-    plane::create_plane, playground::PlaygroundPlugin, plugin::SimulationPlugin, ui::UIPlugin,
+    plane::create_plane, playground::PlaygroundPlugin, plugin::SimulationPlugin,
+    resources::SimulationWorld, ui::UIPlugin,
 };
 
 mod genetic_algorithm;
@@ -30,13 +31,19 @@ fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    world: Res<SimulationWorld>,
 ) {
     // Camera
     commands
         .spawn_bundle(OrthographicCameraBundle::new_2d())
         .insert(MainCamera);
 
-    create_plane(&mut commands, &mut materials, asset_server);
+    create_plane(
+        &mut commands,
+        &mut materials,
+        asset_server,
+        world.0.settings(),
+    );
 }
 
 fn camera_movement(keys: Res<Input<KeyCode>>, mut camera: ResMut<CameraTransform>) {
@@ -125,10 +132,10 @@ fn main() {
     if options.headless {
         info!("Running in headless mode");
 
-        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(
-            1.0 / 60.0,
-        )))
-        .add_plugins(MinimalPlugins);
+        // The simulation is not tied to the wall clock in headless mode,
+        // so updates run as fast as possible
+        app.insert_resource(ScheduleRunnerSettings::run_loop(Duration::ZERO))
+            .add_plugins(MinimalPlugins);
     } else {
         app.add_plugins(DefaultPlugins)
             .insert_resource(CameraTransform {
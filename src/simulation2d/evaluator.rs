@@ -0,0 +1,109 @@
+use bevy::tasks::{TaskPool, TaskPoolBuilder};
+
+use crate::genetic_algorithm::creature_chromosome::CreatureChromosome;
+
+use super::{
+    events::FinishedEvaluatingEvent,
+    world::{World2d, WorldSettings},
+};
+
+/// Evaluates batches of creatures split across worker threads,
+/// every creature is simulated in its own world
+pub struct ParallelEvaluator {
+    pool: TaskPool,
+    threads: usize,
+    settings: WorldSettings,
+}
+
+impl ParallelEvaluator {
+    pub fn new(threads: usize, settings: WorldSettings) -> ParallelEvaluator {
+        let threads = threads.max(1);
+
+        ParallelEvaluator {
+            pool: TaskPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name("Evaluator".to_string())
+                .build(),
+            threads,
+            settings,
+        }
+    }
+
+    /// Returns the results of the chromosomes, in the same order they were given
+    pub fn evaluate(&self, chromosomes: &[CreatureChromosome]) -> Vec<FinishedEvaluatingEvent> {
+        if chromosomes.is_empty() {
+            return Vec::new();
+        }
+
+        let chunk_size = (chromosomes.len() + self.threads - 1) / self.threads;
+        let settings = &self.settings;
+
+        self.pool
+            .scope(|scope| {
+                for chunk in chromosomes.chunks(chunk_size) {
+                    scope.spawn(async move {
+                        chunk
+                            .iter()
+                            .map(|chromosome| {
+                                let evaluation = World2d::evaluate(chromosome, settings.clone());
+                                FinishedEvaluatingEvent {
+                                    chromosome: CreatureChromosome {
+                                        fitness: evaluation.fitness,
+                                        behaviour: evaluation.behaviour,
+                                        ..chromosome.clone()
+                                    },
+                                    energy: evaluation.energy,
+                                    failure: evaluation.failure,
+                                }
+                            })
+                            .collect::<Vec<FinishedEvaluatingEvent>>()
+                    });
+                }
+            })
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        config::CONFIG,
+        genetic_algorithm::{creature_chromosome::CreatureChromosome, operations::RandomCreatable},
+    };
+
+    #[test]
+    fn test_parallel_evaluation_matches_sequential() {
+        let settings = WorldSettings::new(&CONFIG.instances[0]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let chromosomes: Vec<CreatureChromosome> = (0..7)
+            .map(|_| CreatureChromosome::random(&mut rng))
+            .collect();
+
+        let evaluated: Vec<(CreatureChromosome, f32)> = ParallelEvaluator::new(3, settings.clone())
+            .evaluate(&chromosomes)
+            .into_iter()
+            .map(|event| (event.chromosome, event.energy))
+            .collect();
+
+        let expected: Vec<(CreatureChromosome, f32)> = chromosomes
+            .iter()
+            .map(|chromosome| {
+                let evaluation = World2d::evaluate(chromosome, settings.clone());
+                let chromosome = CreatureChromosome {
+                    fitness: evaluation.fitness,
+                    behaviour: evaluation.behaviour,
+                    ..chromosome.clone()
+                };
+
+                (chromosome, evaluation.energy)
+            })
+            .collect();
+
+        assert_eq!(evaluated, expected);
+    }
+}
@@ -1,11 +1,18 @@
 use crate::genetic_algorithm::creature_chromosome::CreatureChromosome;
 
+use super::world::body::Failure;
+
 pub struct StartEvaluatingEvent {
     pub chromosomes: Vec<CreatureChromosome>,
 }
 
 pub struct FinishedEvaluatingEvent {
+    /// Chromosome with its fitness set
     pub chromosome: CreatureChromosome,
+    /// Work done by the creature's muscles during the evaluation
+    pub energy: f32,
+    /// Why the creature was disqualified, if it was
+    pub failure: Option<Failure>,
 }
 
 pub struct InitializeEvent;
@@ -1,10 +1,11 @@
 pub mod creature;
+pub mod evaluator;
 pub mod events;
 pub mod muscle;
 pub mod node;
-pub mod physics;
 pub mod plane;
 pub mod playground;
 pub mod plugin;
 pub mod resources;
 pub mod ui;
+pub mod world;
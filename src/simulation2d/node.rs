@@ -1,72 +1,61 @@
 use bevy::prelude::*;
 
-use crate::genetic_algorithm::node_phenotype::NodePhenotype;
-
-use super::physics::Velocity;
+use super::{resources::SimulationWorld, world::body::BodyNode};
 
+/// Renders a node of a body in the simulation world
 pub struct Node {
-    pub radius: f32,
-    pub friction: f32,
-}
-
-#[derive(Bundle)]
-pub struct NodeBundle {
-    node: Node,
-    velocity: Velocity,
-
-    #[bundle]
-    sprite: SpriteBundle,
+    /// Index of the body in the world
+    pub body: usize,
+    /// Index of the node in the body
+    pub index: usize,
 }
 
 pub fn create_node(
     parent: &mut ChildBuilder,
-    node_phenotype: &NodePhenotype,
-    _meshes: &mut ResMut<Assets<Mesh>>,
+    body_index: usize,
+    node_index: usize,
+    body_node: &BodyNode,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     asset_server: &Res<AssetServer>,
-    node_size: f32,
 ) -> Entity {
     let texture_handle = asset_server.load("circle.png");
 
     parent
         .spawn()
         .insert(Node {
-            radius: node_size,
-            friction: node_phenotype.friction,
+            body: body_index,
+            index: node_index,
         })
-        .insert(Velocity(Vec3::default()))
         .insert_bundle(SpriteBundle {
             material: materials.add(texture_handle.into()),
             sprite: Sprite {
-                size: Vec2::new(node_size / 2.0, node_size / 2.0),
+                // Drawn as big as the nodes collide, so touching nodes look touching
+                size: Vec2::new(body_node.radius * 2.0, body_node.radius * 2.0),
                 resize_mode: SpriteResizeMode::Manual,
                 ..Default::default()
             },
-            transform: Transform::from_translation(Vec3::new(
-                node_phenotype.position.x,
-                node_phenotype.position.y,
-                0.0,
-            )),
+            transform: Transform::from_translation(body_node.position.extend(0.0)),
             ..Default::default()
         })
         .id()
 }
 
-pub fn create_node_headless(
-    parent: &mut ChildBuilder,
-    node_phenotype: &NodePhenotype,
-    node_size: f32,
-) -> Entity {
-    parent
-        .spawn()
-        .insert(Node {
-            radius: node_size,
-            friction: node_phenotype.friction,
-        })
-        .insert(Velocity(Vec3::default()))
-        .insert(Transform {
-            translation: Vec3::new(node_phenotype.position.x, node_phenotype.position.y, 0.0),
-            ..Default::default()
-        })
-        .id()
+/// Moves node sprites to their position in the simulation world
+pub fn update_node_transforms(
+    world: Res<SimulationWorld>,
+    mut nodes: Query<(&Node, &mut Transform)>,
+) {
+    let span = info_span!("system", name = "update_node_transforms");
+    let _guard = span.enter();
+
+    for (node, mut transform) in nodes.iter_mut() {
+        if let Some(body) = world
+            .0
+            .bodies()
+            .get(node.body)
+            .filter(|body| body.failure.is_none())
+        {
+            transform.translation = body.nodes[node.index].position.extend(0.0);
+        }
+    }
 }
@@ -1,10 +1,25 @@
 use bevy::prelude::*;
+use bevy_prototype_debug_lines::DebugLines;
 
+use crate::config::Obstacle;
+
+use super::{
+    resources::SimulationWorld,
+    world::{obstacle::ground_height, terrain::Terrain, WorldSettings},
+};
+
+/// Spawns the ground sprite, only flat terrain without pits has one.
+/// Any other ground is drawn by `draw_terrain`
 pub fn create_plane(
     commands: &mut Commands,
     materials: &mut ResMut<Assets<ColorMaterial>>,
     asset_server: Res<AssetServer>,
+    settings: &WorldSettings,
 ) {
+    if !is_plain_ground(settings) {
+        return;
+    }
+
     let texture_handle = asset_server.load("ground.png");
     commands.spawn_bundle(SpriteBundle {
         material: materials.add(texture_handle.into()),
@@ -17,3 +32,76 @@ pub fn create_plane(
         ..Default::default()
     });
 }
+
+fn is_plain_ground(settings: &WorldSettings) -> bool {
+    settings.terrain == Terrain::Flat
+        && !settings
+            .obstacles
+            .iter()
+            .any(|obstacle| matches!(obstacle, Obstacle::Pit { .. }))
+}
+
+/// Draws the profile of the ground as a line, pits included
+pub fn draw_terrain(world: Res<SimulationWorld>, mut lines: ResMut<DebugLines>) {
+    const HALF_WIDTH: f32 = 10.0;
+    const RESOLUTION: f32 = 0.05;
+
+    let span = info_span!("system", name = "draw_terrain");
+    let _guard = span.enter();
+
+    let settings = world.0.settings();
+    if is_plain_ground(settings) {
+        return;
+    }
+
+    let terrain = &settings.terrain;
+    let segments = (2.0 * HALF_WIDTH / RESOLUTION) as usize;
+    let mut points: Vec<Vec2> = (0..=segments)
+        .map(|segment| -HALF_WIDTH + segment as f32 * RESOLUTION)
+        .map(|x| Vec2::new(x, ground_height(terrain, &settings.obstacles, x)))
+        .collect();
+
+    // Pit walls are vertical, so both of their ends are added at the same x
+    for obstacle in settings.obstacles.iter() {
+        if let Obstacle::Pit {
+            start,
+            width,
+            depth,
+        } = *obstacle
+        {
+            let end = start + width;
+            points.push(Vec2::new(start, terrain.height(start)));
+            points.push(Vec2::new(start, terrain.height(start) - depth));
+            points.push(Vec2::new(end, terrain.height(end) - depth));
+            points.push(Vec2::new(end, terrain.height(end)));
+        }
+    }
+
+    // Stable sort keeps the order of the wall ends
+    points.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+
+    for pair in points.windows(2) {
+        lines.line(pair[0].extend(0.0), pair[1].extend(0.0), 0.0);
+    }
+}
+
+/// Draws the outline of every box
+pub fn draw_obstacles(world: Res<SimulationWorld>, mut lines: ResMut<DebugLines>) {
+    let span = info_span!("system", name = "draw_obstacles");
+    let _guard = span.enter();
+
+    for obstacle in world.0.settings().obstacles.iter() {
+        if let Obstacle::Box { min, max } = *obstacle {
+            let corners = [
+                Vec3::new(min.x, min.y, 0.0),
+                Vec3::new(max.x, min.y, 0.0),
+                Vec3::new(max.x, max.y, 0.0),
+                Vec3::new(min.x, max.y, 0.0),
+            ];
+
+            for index in 0..corners.len() {
+                lines.line(corners[index], corners[(index + 1) % corners.len()], 0.0);
+            }
+        }
+    }
+}
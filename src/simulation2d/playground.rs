@@ -1,41 +1,51 @@
 use std::fs::File;
 
 use bevy::{app::AppExit, prelude::*};
-use ron::de::from_reader;
+use rand::{rngs::StdRng, SeedableRng};
+use ron::{
+    de::from_reader,
+    ser::{to_string_pretty, PrettyConfig},
+};
+use structopt::StructOpt;
 
 use crate::{
+    arguments::Opt,
     config::CONFIG,
     genetic_algorithm::{
         creature_chromosome::CreatureChromosome,
         operations::{Correctable, RandomCreatable},
     },
-    simulation2d::plugin::calculate_creatures_position,
 };
 
 use super::{
-    creature::{create_creature, Creature},
-    node::Node,
-    resources::EvaluationStopwatch,
+    creature::create_creature,
+    events::FinishedEvaluatingEvent,
+    resources::SimulationWorld,
+    world::{sensors::Sensors, World2d, WorldSettings},
 };
 
 pub struct PlaygroundPlugin;
 
 impl Plugin for PlaygroundPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(load_creature_from_file.system())
-            .add_system(check_should_end.system());
+        let options = Opt::from_args();
+
+        if options.headless {
+            app.add_startup_system(evaluate_creature_from_file.system());
+        } else {
+            app.add_startup_system(load_creature_from_file.system())
+                .add_system(check_should_end.system());
+        }
     }
 }
 
-fn load_creature_from_file(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
-) {
-    let chromosome = match read_chromosome() {
+fn load_chromosome() -> CreatureChromosome {
+    let options = Opt::from_args();
+    let mut rng = StdRng::seed_from_u64(options.seed.unwrap_or_else(rand::random));
+
+    match read_chromosome() {
         Ok(mut chromosome) => {
-            chromosome.correct();
+            chromosome.correct(&mut rng);
             chromosome
         }
         Err(error) => {
@@ -43,17 +53,58 @@ fn load_creature_from_file(
                 "Chromosome file error. {}. Using random chromosome.",
                 error.code
             );
-            CreatureChromosome::random()
+            CreatureChromosome::random(&mut rng)
+        }
+    }
+}
+
+/// Simulates the creature without rendering it and prints its fitness.
+/// Its sensor readings are saved if a file to record them to was given
+fn evaluate_creature_from_file(mut app_exit_events: EventWriter<AppExit>) {
+    let options = Opt::from_args();
+    let mut world = World2d::new(WorldSettings::new(&CONFIG.instances[options.instance]));
+    let index = world.add_creature(&load_chromosome());
+    let mut readings = vec![world.bodies()[index].sensors.clone()];
+
+    while world.is_evaluating() {
+        world.step();
+        readings.push(world.bodies()[index].sensors.clone());
+    }
+
+    println!("{}", world.fitness(index));
+    if let Some(failure) = world.bodies()[index].failure {
+        println!("Failed: {:?}", failure);
+    }
+
+    if let Some(path) = options.record_sensors {
+        if let Err(error) = write_readings(&path, &readings) {
+            warn!("Couldn't record sensors to {}. {}", path, error);
         }
-    };
-    let node_size = CONFIG.node_size;
+    }
+
+    app_exit_events.send(AppExit);
+}
+
+fn write_readings(path: &str, readings: &[Sensors]) -> Result<(), Box<dyn std::error::Error>> {
+    let readings = to_string_pretty(&readings, PrettyConfig::default())?;
+    std::fs::write(path, readings)?;
+    Ok(())
+}
+
+fn load_creature_from_file(
+    mut commands: Commands,
+    mut world: ResMut<SimulationWorld>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let chromosome = load_chromosome();
+    let body_index = world.0.add_creature(&chromosome);
     create_creature(
         &mut commands,
-        chromosome,
-        &mut meshes,
+        body_index,
+        &world.0.bodies()[body_index],
         &mut materials,
         &asset_server,
-        node_size,
     );
 }
 
@@ -65,17 +116,17 @@ fn read_chromosome() -> Result<CreatureChromosome, ron::error::Error> {
 }
 
 fn check_should_end(
-    evaluation_stopwatch: Res<EvaluationStopwatch>,
+    mut finished_evaluating_events: EventReader<FinishedEvaluatingEvent>,
     mut app_exit_events: EventWriter<AppExit>,
-    query: Query<Entity, With<Creature>>,
-    collider_node_positions: Query<(&Transform, &Parent), With<Node>>,
 ) {
-    if evaluation_stopwatch.0.elapsed_secs() >= CONFIG.evaluation_time {
-        let entity = query.single().unwrap();
+    for event in finished_evaluating_events.iter() {
         info!(
-            "{}",
-            calculate_creatures_position(entity, &collider_node_positions)
+            "Fitness: {}, energy: {}",
+            event.chromosome.fitness, event.energy
         );
+        if let Some(failure) = event.failure {
+            info!("Failed: {:?}", failure);
+        }
         app_exit_events.send(AppExit);
     }
 }
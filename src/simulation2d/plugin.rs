@@ -1,171 +1,224 @@
-use std::time::Duration;
-
-use bevy::{core::FixedTimestep, prelude::*};
+use bevy::{
+    core::FixedTimestep,
+    ecs::schedule::{RunCriteriaDescriptor, ShouldRun},
+    prelude::*,
+};
 
 use structopt::StructOpt;
 
-use crate::{arguments::Opt, config::CONFIG};
+use crate::{
+    arguments::Opt,
+    config::{NodeCollisions, CONFIG},
+};
 
 use super::{
-    creature::{create_creature, create_creature_headless, Creature},
+    creature::{create_creature, Creature},
+    evaluator::ParallelEvaluator,
     events::{FinishedEvaluatingEvent, InitializeEvent, StartEvaluatingEvent},
     muscle::MusclePlugin,
-    node,
-    physics::PhysicsPlugin,
-    resources::{EvaluationStopwatch, GenerationCount, RealTimeStopwatch},
+    node::update_node_transforms,
+    plane::{draw_obstacles, draw_terrain},
+    resources::{GenerationCount, RealTimeStopwatch, SimulationWorld, StepsPerUpdate},
+    world::{World2d, WorldSettings},
 };
 
+/// Label of the run criteria that decides when a fixed simulation step happens.
+/// Every system that advances the simulation runs under it.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RunCriteriaLabel)]
+pub struct SimulationStep;
+
 pub struct SimulationPlugin;
 
 impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut AppBuilder) {
         let options = Opt::from_args();
-        let selected_instance = &CONFIG.instances[options.instance];
+        let settings = WorldSettings::new(&CONFIG.instances[options.instance]);
 
         app.add_plugin(MusclePlugin)
-            .add_plugin(PhysicsPlugin::new(
-                selected_instance.gravity,
-                selected_instance.air_friction,
-            ))
             .add_event::<StartEvaluatingEvent>()
             .add_event::<FinishedEvaluatingEvent>()
             .add_event::<InitializeEvent>()
-            .insert_resource(EvaluationStopwatch::default())
+            .insert_resource(SimulationWorld(World2d::new(settings.clone())))
             .insert_resource(GenerationCount::default())
             .insert_resource(RealTimeStopwatch::default())
+            .insert_resource(StepsPerUpdate(options.steps_per_update))
             .add_system(evaluate_simulation.system())
             .add_system(real_stopwatch_ticker.system())
             .add_system_set(
                 SystemSet::new()
-                    .with_run_criteria(FixedTimestep::step(
-                        CONFIG.fixed_time_step as f64 / CONFIG.time_scale as f64,
-                    ))
-                    .with_system(tick_stopwatch.system()),
+                    .with_run_criteria(simulation_step_criteria(options.headless))
+                    .with_system(step_world.system()),
             );
 
-        let options = Opt::from_args();
-
-        if options.headless {
-            app.add_system(simulate_headless.system());
+        // Every thread simulates its creatures in their own worlds,
+        // so creatures only meet when they share the app's world
+        let threads = if options.threads > 1
+            && settings.node_collisions == NodeCollisions::AllCreatures
+        {
+            warn!("Creatures can't collide with each other on several threads. Using a single thread.");
+            1
         } else {
-            app.add_system(simulate.system());
+            options.threads
+        };
+
+        // Creatures are spawned in a previous stage so they already exist
+        // when the simulation steps run in the same frame
+        if options.headless && threads > 1 {
+            app.insert_resource(ParallelEvaluator::new(threads, settings))
+                .add_system_to_stage(CoreStage::PreUpdate, simulate_parallel.system());
+        } else if options.headless {
+            app.add_system_to_stage(CoreStage::PreUpdate, simulate_headless.system());
+        } else {
+            app.add_system_to_stage(CoreStage::PreUpdate, simulate.system())
+                .add_system(update_node_transforms.system())
+                .add_system(draw_terrain.system())
+                .add_system(draw_obstacles.system());
         }
     }
 }
 
+/// Creates the run criteria for the simulation steps.
+/// With a window the simulation follows the wall clock, scaled by `time_scale`.
+/// In headless mode it is not throttled at all, see `unthrottled_step`.
+fn simulation_step_criteria(headless: bool) -> RunCriteriaDescriptor {
+    if headless {
+        unthrottled_step.system().label(SimulationStep)
+    } else {
+        FixedTimestep::step(CONFIG.fixed_time_step as f64 / CONFIG.time_scale as f64)
+            .label(SimulationStep)
+    }
+}
+
+/// Runs simulation steps back to back, regardless of how much real time has passed.
+/// Stops after `StepsPerUpdate` steps, or when the current evaluation is over if it is 0
+fn unthrottled_step(
+    mut steps_taken: Local<usize>,
+    steps_per_update: Res<StepsPerUpdate>,
+    world: Res<SimulationWorld>,
+) -> ShouldRun {
+    let update_over = steps_per_update.0 != 0 && *steps_taken >= steps_per_update.0;
+
+    if !world.0.is_evaluating() || update_over {
+        *steps_taken = 0;
+        return ShouldRun::No;
+    }
+
+    *steps_taken += 1;
+    ShouldRun::YesAndCheckAgain
+}
+
+fn step_world(mut world: ResMut<SimulationWorld>) {
+    let span = info_span!("system", name = "step_world");
+    let _guard = span.enter();
+
+    if world.0.is_evaluating() {
+        world.0.step();
+    }
+}
+
 fn simulate(
     mut commands: Commands,
     creatures: Query<Entity, With<Creature>>,
     mut start_evaluating_events: EventReader<StartEvaluatingEvent>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut world: ResMut<SimulationWorld>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    mut stopwatch: ResMut<EvaluationStopwatch>,
     asset_server: Res<AssetServer>,
 ) {
     let span = info_span!("system", name = "simulate");
     let _guard = span.enter();
 
     for event in start_evaluating_events.iter() {
+        for entity in creatures.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        world.0.clear();
+
         for chromosome in event.chromosomes.iter() {
-            for entity in creatures.iter() {
-                commands.entity(entity).despawn_recursive();
-            }
+            let body_index = world.0.add_creature(chromosome);
 
             create_creature(
                 &mut commands,
-                chromosome.clone(),
-                &mut meshes,
+                body_index,
+                &world.0.bodies()[body_index],
                 &mut materials,
                 &asset_server,
-                CONFIG.node_size,
             );
         }
-
-        stopwatch.0.reset();
-        stopwatch.0.unpause();
     }
 }
 
 fn simulate_headless(
-    mut commands: Commands,
-    creatures: Query<Entity, With<Creature>>,
     mut start_evaluating_events: EventReader<StartEvaluatingEvent>,
-    mut stopwatch: ResMut<EvaluationStopwatch>,
+    mut world: ResMut<SimulationWorld>,
     mut real_stopwatch: ResMut<RealTimeStopwatch>,
 ) {
     let span = info_span!("system", name = "simulate");
     let _guard = span.enter();
 
     for event in start_evaluating_events.iter() {
-        for entity in creatures.iter() {
-            commands.entity(entity).despawn_recursive();
-        }
+        world.0.clear();
 
         for chromosome in event.chromosomes.iter() {
-            create_creature_headless(&mut commands, chromosome.clone(), CONFIG.node_size);
+            world.0.add_creature(chromosome);
         }
 
         info!("Time spent: {:?}", real_stopwatch.0.elapsed());
-        stopwatch.0.reset();
-        stopwatch.0.unpause();
-
         real_stopwatch.0.reset();
     }
 }
 
-fn tick_stopwatch(mut stopwatch: ResMut<EvaluationStopwatch>) {
-    let span = info_span!("system", name = "tick_stopwatch");
+/// Evaluates every creature of the batch right away, on the evaluator's threads
+fn simulate_parallel(
+    mut start_evaluating_events: EventReader<StartEvaluatingEvent>,
+    mut finished_evaluating_events: EventWriter<FinishedEvaluatingEvent>,
+    evaluator: Res<ParallelEvaluator>,
+    mut real_stopwatch: ResMut<RealTimeStopwatch>,
+) {
+    let span = info_span!("system", name = "simulate_parallel");
     let _guard = span.enter();
 
-    stopwatch
-        .0
-        .tick(Duration::from_nanos((CONFIG.fixed_time_step * 1e9) as u64));
+    for event in start_evaluating_events.iter() {
+        for finished_evaluating_event in evaluator.evaluate(&event.chromosomes) {
+            finished_evaluating_events.send(finished_evaluating_event);
+        }
+
+        info!("Time spent: {:?}", real_stopwatch.0.elapsed());
+        real_stopwatch.0.reset();
+    }
 }
 
 fn real_stopwatch_ticker(mut real_stopwatch: ResMut<RealTimeStopwatch>, time: Res<Time>) {
     real_stopwatch.0.tick(time.delta());
 }
 
-/// Calculates creature's position averaging its nodes positions
-pub fn calculate_creatures_position(
-    entity: Entity,
-    collider_node_positions: &Query<(&Transform, &Parent), With<node::Node>>,
-) -> f32 {
-    let span = info_span!("helper", name = "calculate_creatures_position");
-    let _guard = span.enter();
-
-    let (creature_node_count, positions_sum) = collider_node_positions
-        .iter()
-        .filter(|(_, parent)| parent.0 == entity)
-        .fold((0, 0.0), |(count, sum), (collider_position, _)| {
-            (count + 1, sum + collider_position.translation.x)
-        });
-    positions_sum / creature_node_count as f32
-}
-
 fn evaluate_simulation(
     mut commands: Commands,
-    mut stopwatch: ResMut<EvaluationStopwatch>,
-    mut creatures: Query<(Entity, &mut Creature)>,
-    collider_node_positions: Query<(&Transform, &Parent), With<node::Node>>,
+    mut world: ResMut<SimulationWorld>,
+    creatures: Query<Entity, With<Creature>>,
     mut finished_evaluating_events: EventWriter<FinishedEvaluatingEvent>,
 ) {
     let span = info_span!("system", name = "evaluate_simulation");
     let _guard = span.enter();
 
-    if stopwatch.0.paused() || stopwatch.0.elapsed_secs() <= CONFIG.evaluation_time {
+    if world.0.bodies().is_empty() || !world.0.is_finished() {
         return;
     }
 
-    for (entity, mut creature) in creatures.iter_mut() {
-        let position_x = calculate_creatures_position(entity, &collider_node_positions);
-        creature.chromosome.fitness = position_x.abs();
+    for (index, body) in world.0.bodies().iter().enumerate() {
+        let mut chromosome = body.chromosome.clone();
+        chromosome.fitness = world.0.fitness(index);
+        chromosome.behaviour = body.behaviour();
         finished_evaluating_events.send(FinishedEvaluatingEvent {
-            chromosome: creature.chromosome.clone(),
+            chromosome,
+            energy: body.energy(),
+            failure: body.failure,
         });
+    }
 
+    for entity in creatures.iter() {
         commands.entity(entity).despawn_recursive();
     }
 
-    stopwatch.0.pause();
+    world.0.clear();
 }
@@ -1,10 +1,16 @@
 use bevy::core::Stopwatch;
 
-#[derive(Default)]
-pub struct EvaluationStopwatch(pub Stopwatch);
+use super::world::World2d;
+
+/// World every creature being evaluated is simulated in
+pub struct SimulationWorld(pub World2d);
 
 #[derive(Default)]
 pub struct RealTimeStopwatch(pub Stopwatch);
 
 #[derive(Default)]
 pub struct GenerationCount(pub usize);
+
+/// Amount of simulation steps run per app update in headless mode.
+/// 0 means every step of an evaluation runs in a single update
+pub struct StepsPerUpdate(pub usize);
@@ -3,7 +3,7 @@ use bevy::{
     prelude::*,
 };
 
-use super::resources::{EvaluationStopwatch, GenerationCount};
+use super::resources::{GenerationCount, SimulationWorld};
 
 struct TimerText;
 struct GenerationText;
@@ -163,12 +163,9 @@ fn update_generation_text(
     }
 }
 
-fn update_timer_text(
-    stopwatch: Res<EvaluationStopwatch>,
-    mut query: Query<&mut Text, With<TimerText>>,
-) {
+fn update_timer_text(world: Res<SimulationWorld>, mut query: Query<&mut Text, With<TimerText>>) {
     for mut text in query.iter_mut() {
-        text.sections[0].value = format!("Time: {:.2}", stopwatch.0.elapsed_secs());
+        text.sections[0].value = format!("Time: {:.2}", world.0.elapsed_secs());
     }
 }
 
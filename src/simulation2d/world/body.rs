@@ -0,0 +1,237 @@
+use bevy::math::Vec2;
+
+use crate::config::FitnessFunction;
+use crate::genetic_algorithm::{
+    behaviour::Behaviour,
+    bone_phenotype::BonePhenotype,
+    creature_chromosome::CreatureChromosome,
+    muscle_phenotype::{MusclePhenotype, Trigger, Waveform},
+    node_phenotype::NodePhenotype,
+};
+
+use super::sensors::Sensors;
+
+/// Simulated state of a creature's node
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BodyNode {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+    pub friction: f32,
+    pub mass: f32,
+}
+
+impl BodyNode {
+    fn from_phenotype(node_phenotype: &NodePhenotype, radius: f32) -> BodyNode {
+        BodyNode {
+            position: node_phenotype.position,
+            velocity: Vec2::ZERO,
+            radius,
+            friction: node_phenotype.friction,
+            mass: node_phenotype.mass,
+        }
+    }
+}
+
+/// Simulated muscle, pulls/pushes two nodes of the same body
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BodyMuscle {
+    pub contracted_time: f32,
+    pub contracted_length: f32,
+    pub extended_length: f32,
+    pub strength: f32,
+    pub damping: f32,
+    pub phase: f32,
+    pub waveform: Waveform,
+    pub controller_weights: Vec<f32>,
+    /// Length the neural network controller chose for the current step
+    pub controlled_length: f32,
+    /// Index of the clock of the body the muscle follows
+    pub clock: usize,
+    pub trigger: Trigger,
+    /// Seconds left of the contraction started by the last trigger
+    pub reflex_time: f32,
+    /// Indexes of the nodes in the body
+    pub nodes: (usize, usize),
+    /// Work done by the muscle since the evaluation started
+    pub energy: f32,
+}
+
+impl BodyMuscle {
+    fn from_phenotype(muscle_phenotype: &MusclePhenotype) -> BodyMuscle {
+        BodyMuscle {
+            contracted_time: muscle_phenotype.contracted_time,
+            contracted_length: muscle_phenotype.contracted_length,
+            extended_length: muscle_phenotype.extended_length,
+            strength: muscle_phenotype.strength,
+            damping: muscle_phenotype.damping,
+            phase: muscle_phenotype.phase,
+            waveform: muscle_phenotype.waveform,
+            controller_weights: muscle_phenotype.controller_weights.clone(),
+            controlled_length: muscle_phenotype.extended_length,
+            clock: muscle_phenotype.clock,
+            trigger: muscle_phenotype.trigger,
+            reflex_time: 0.0,
+            nodes: muscle_phenotype.nodes,
+            energy: 0.0,
+        }
+    }
+}
+
+/// Simulated bone, keeps two nodes of the same body at a fixed distance
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BodyBone {
+    pub length: f32,
+    /// Indexes of the nodes in the body
+    pub nodes: (usize, usize),
+}
+
+impl BodyBone {
+    fn from_phenotype(bone_phenotype: &BonePhenotype, nodes: &[NodePhenotype]) -> BodyBone {
+        let (first, second) = bone_phenotype.nodes;
+
+        BodyBone {
+            length: nodes[first].position.distance(nodes[second].position),
+            nodes: bone_phenotype.nodes,
+        }
+    }
+}
+
+/// Why the simulation of a body was stopped before the evaluation ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    /// A node position or velocity became NaN or infinite
+    NotFinite,
+    /// A node moved faster than the maximum node speed of the config
+    Runaway,
+}
+
+/// Simulated creature
+#[derive(Debug, Default, Clone)]
+pub struct Body {
+    /// Chromosome the body was built from
+    pub chromosome: CreatureChromosome,
+    pub nodes: Vec<BodyNode>,
+    pub muscles: Vec<BodyMuscle>,
+    pub bones: Vec<BodyBone>,
+    /// Time of every clock, each one goes from 0 to its size
+    pub clocks: Vec<f32>,
+    pub clock_sizes: Vec<f32>,
+    /// Set when the body blew up, it isn't simulated anymore
+    pub failure: Option<Failure>,
+    /// Readings taken at the end of the last step
+    pub sensors: Sensors,
+    /// Highest the position of the body got during the evaluation
+    pub max_height: f32,
+    /// Seconds nodes spent touching the ground, averaged over the nodes
+    pub ground_contact_time: f32,
+    /// Seconds simulated since the evaluation started
+    pub elapsed_time: f32,
+}
+
+impl Body {
+    /// Builds the body of a chromosome, `node_size` is the diameter of its nodes
+    pub fn new(chromosome: &CreatureChromosome, node_size: f32) -> Body {
+        Body {
+            chromosome: chromosome.clone(),
+            nodes: chromosome
+                .nodes
+                .iter()
+                .map(|node| BodyNode::from_phenotype(node, node_size / 2.0))
+                .collect(),
+            muscles: chromosome
+                .muscles
+                .iter()
+                .map(BodyMuscle::from_phenotype)
+                .collect(),
+            bones: chromosome
+                .bones
+                .iter()
+                .map(|bone| BodyBone::from_phenotype(bone, &chromosome.nodes))
+                .collect(),
+            clocks: vec![0.0; chromosome.extra_clock_sizes.len() + 1],
+            clock_sizes: chromosome.clock_sizes(),
+            failure: None,
+            sensors: Sensors::default(),
+            max_height: 0.0,
+            ground_contact_time: 0.0,
+            elapsed_time: 0.0,
+        }
+    }
+
+    /// Fraction of the cycle of a clock that has passed.
+    /// A clock without length stays at the start of its cycle
+    pub fn clock_cycle(&self, clock: usize) -> f32 {
+        if self.clock_sizes[clock] > 0.0 {
+            self.clocks[clock] / self.clock_sizes[clock]
+        } else {
+            0.0
+        }
+    }
+
+    /// Calculates body's position averaging its nodes positions
+    pub fn position(&self) -> Vec2 {
+        let positions_sum = self
+            .nodes
+            .iter()
+            .fold(Vec2::ZERO, |sum, node| sum + node.position);
+        positions_sum / self.nodes.len() as f32
+    }
+
+    /// Current length of every muscle
+    pub fn muscle_lengths(&self) -> Vec<f32> {
+        self.muscles
+            .iter()
+            .map(|muscle| {
+                (self.nodes[muscle.nodes.1].position - self.nodes[muscle.nodes.0].position).length()
+            })
+            .collect()
+    }
+
+    /// Work done by all the muscles since the evaluation started
+    pub fn energy(&self) -> f32 {
+        self.muscles.iter().map(|muscle| muscle.energy).sum()
+    }
+
+    /// Adds what the body did in the last step to its behaviour,
+    /// after its sensors have been read
+    pub fn record_behaviour(&mut self, delta_time: f32) {
+        let contacts = self
+            .sensors
+            .nodes
+            .iter()
+            .filter(|node| node.ground_contact)
+            .count();
+
+        self.max_height = self.max_height.max(self.position().y);
+        self.ground_contact_time += delta_time * contacts as f32 / self.nodes.len().max(1) as f32;
+        self.elapsed_time += delta_time;
+    }
+
+    /// Bodies that failed behave as if they didn't move
+    pub fn behaviour(&self) -> Behaviour {
+        if self.failure.is_some() || self.elapsed_time <= 0.0 {
+            return Behaviour::default();
+        }
+
+        Behaviour {
+            final_position: self.position(),
+            max_height: self.max_height,
+            ground_contact: self.ground_contact_time / self.elapsed_time,
+        }
+    }
+
+    /// Bodies that failed get no fitness, as if they didn't move
+    pub fn fitness(&self, fitness_function: FitnessFunction) -> f32 {
+        if self.failure.is_some() {
+            return 0.0;
+        }
+
+        let distance = self.position().x.abs();
+
+        match fitness_function {
+            FitnessFunction::Distance => distance,
+            FitnessFunction::DistancePerEnergy => distance / (1.0 + self.energy()),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use super::body::Body;
+
+/// Times every bone is corrected per step, more iterations make chains of bones stiffer
+const ITERATIONS: usize = 10;
+
+/// Moves the nodes joined by bones back to the length of the bones.
+/// Nodes also lose the velocity that would stretch or compress the bones.
+/// Heavier nodes are moved less
+pub fn solve_bones(body: &mut Body) {
+    for _ in 0..ITERATIONS {
+        for bone in body.bones.iter() {
+            let (first, second) = bone.nodes;
+            let first_node = &body.nodes[first];
+            let second_node = &body.nodes[second];
+
+            let offset = second_node.position - first_node.position;
+            let distance = offset.length();
+            if distance == 0.0 {
+                continue;
+            }
+
+            let direction = offset / distance;
+            let total_inverse_mass = 1.0 / first_node.mass + 1.0 / second_node.mass;
+            let first_share = (1.0 / first_node.mass) / total_inverse_mass;
+            let second_share = (1.0 / second_node.mass) / total_inverse_mass;
+
+            let stretch = direction * (distance - bone.length);
+            let stretching_velocity =
+                direction * (second_node.velocity - first_node.velocity).dot(direction);
+
+            body.nodes[first].position += stretch * first_share;
+            body.nodes[first].velocity += stretching_velocity * first_share;
+            body.nodes[second].position -= stretch * second_share;
+            body.nodes[second].velocity -= stretching_velocity * second_share;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec2;
+
+    use super::*;
+    use crate::simulation2d::world::body::{BodyBone, BodyNode};
+
+    #[test]
+    fn test_bone_restores_its_length() {
+        let node = |x: f32, velocity: f32| BodyNode {
+            position: Vec2::new(x, 0.0),
+            velocity: Vec2::new(velocity, 0.0),
+            mass: 1.0,
+            ..Default::default()
+        };
+        let mut body = Body {
+            nodes: vec![node(0.0, -1.0), node(0.5, 1.0)],
+            bones: vec![BodyBone {
+                length: 0.3,
+                nodes: (0, 1),
+            }],
+            ..Default::default()
+        };
+
+        solve_bones(&mut body);
+
+        assert!((body.nodes[0].position - Vec2::new(0.1, 0.0)).length() < 1e-6);
+        assert!((body.nodes[1].position - Vec2::new(0.4, 0.0)).length() < 1e-6);
+        assert_eq!(body.nodes[0].velocity, Vec2::ZERO);
+        assert_eq!(body.nodes[1].velocity, Vec2::ZERO);
+    }
+}
@@ -0,0 +1,129 @@
+use bevy::math::Vec2;
+
+use super::body::{Body, BodyNode};
+
+/// Pushes two overlapping nodes apart and cancels the velocity
+/// with which they approach each other
+fn resolve_pair(first: &mut BodyNode, second: &mut BodyNode) {
+    let offset = second.position - first.position;
+    let distance = offset.length();
+    let overlap = first.radius + second.radius - distance;
+
+    if overlap <= 0.0 {
+        return;
+    }
+
+    let normal = if distance > 0.0 {
+        offset / distance
+    } else {
+        Vec2::new(0.0, 1.0)
+    };
+
+    first.position -= normal * overlap / 2.0;
+    second.position += normal * overlap / 2.0;
+
+    let approaching_speed = (second.velocity - first.velocity).dot(normal);
+    if approaching_speed < 0.0 {
+        first.velocity += normal * approaching_speed / 2.0;
+        second.velocity -= normal * approaching_speed / 2.0;
+    }
+}
+
+/// Resolves collisions between nodes of the same body.
+/// Nodes joined by a muscle or a bone don't collide, the connection keeps them apart
+pub fn resolve_node_collisions(body: &mut Body) {
+    for first in 0..body.nodes.len() {
+        for second in (first + 1)..body.nodes.len() {
+            let joins =
+                |nodes: (usize, usize)| nodes == (first, second) || nodes == (second, first);
+            let connected = body.muscles.iter().any(|muscle| joins(muscle.nodes))
+                || body.bones.iter().any(|bone| joins(bone.nodes));
+
+            if connected {
+                continue;
+            }
+
+            let (left, right) = body.nodes.split_at_mut(second);
+            resolve_pair(&mut left[first], &mut right[0]);
+        }
+    }
+}
+
+/// Resolves collisions between nodes of different bodies, bodies that failed are left out
+pub fn resolve_body_collisions(bodies: &mut [Body]) {
+    for second_body in 1..bodies.len() {
+        let (left, right) = bodies.split_at_mut(second_body);
+        if right[0].failure.is_some() {
+            continue;
+        }
+
+        for first_body in left.iter_mut().filter(|body| body.failure.is_none()) {
+            for first in first_body.nodes.iter_mut() {
+                for second in right[0].nodes.iter_mut() {
+                    resolve_pair(first, second);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation2d::world::body::{BodyBone, BodyMuscle};
+
+    fn node(x: f32) -> BodyNode {
+        BodyNode {
+            position: Vec2::new(x, 0.0),
+            radius: 0.04,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_overlapping_nodes_are_separated() {
+        let mut body = Body {
+            nodes: vec![node(0.0), node(0.05)],
+            ..Default::default()
+        };
+
+        resolve_node_collisions(&mut body);
+
+        let distance = body.nodes[0].position.distance(body.nodes[1].position);
+        assert!(distance >= 0.08 - f32::EPSILON);
+    }
+
+    #[test]
+    fn test_nodes_joined_by_muscle_do_not_collide() {
+        let mut body = Body {
+            nodes: vec![node(0.0), node(0.05)],
+            muscles: vec![BodyMuscle {
+                nodes: (1, 0),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let before = body.nodes.clone();
+
+        resolve_node_collisions(&mut body);
+
+        assert_eq!(body.nodes, before);
+    }
+
+    #[test]
+    fn test_nodes_joined_by_bone_do_not_collide() {
+        let mut body = Body {
+            nodes: vec![node(0.0), node(0.05)],
+            bones: vec![BodyBone {
+                length: 0.05,
+                nodes: (0, 1),
+            }],
+            ..Default::default()
+        };
+        let before = body.nodes.clone();
+
+        resolve_node_collisions(&mut body);
+
+        assert_eq!(body.nodes, before);
+    }
+}
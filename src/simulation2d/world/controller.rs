@@ -0,0 +1,279 @@
+use std::{collections::HashMap, f32::consts::TAU};
+
+use crate::genetic_algorithm::neat_phenotype::{NeatPhenotype, NeuronKind, NEAT_OUTPUT};
+
+use super::{
+    body::{Body, BodyMuscle},
+    sensors::Sensors,
+};
+
+/// Runs the creature's neural network and stores the length every muscle
+/// should move towards during the step.
+/// Hidden neurons read the internal clock, every muscle's output reads
+/// the sensors of the muscle and its nodes, the clock it follows, and the hidden neurons
+pub fn update_controlled_lengths(body: &mut Body) {
+    let cycles: Vec<f32> = (0..body.clocks.len())
+        .map(|clock| body.clock_cycle(clock))
+        .collect();
+    let angle = cycles[0] * TAU;
+    let clock_inputs = [angle.sin(), angle.cos(), 1.0];
+
+    let hidden: Vec<f32> = body
+        .chromosome
+        .controller
+        .hidden_weights
+        .iter()
+        .map(|weights| weighted_sum(weights, &clock_inputs).tanh())
+        .collect();
+
+    let sensors = &body.sensors;
+
+    for (index, muscle) in body.muscles.iter_mut().enumerate() {
+        let angle = cycles[muscle.clock] * TAU;
+        let inputs: Vec<f32> = muscle_inputs(muscle, index, sensors)
+            .into_iter()
+            .chain([angle.sin(), angle.cos()])
+            .chain(hidden.iter().copied())
+            .collect();
+
+        let contraction = sigmoid(weighted_sum(&muscle.controller_weights, &inputs));
+        set_contraction(muscle, contraction);
+    }
+}
+
+/// Runs the creature's NEAT network once for every muscle and stores the length
+/// it should move towards during the step
+pub fn update_neat_lengths(body: &mut Body) {
+    let network = &body.chromosome.neat_controller;
+    let order = evaluation_order(network);
+    let cycles: Vec<f32> = (0..body.clocks.len())
+        .map(|clock| body.clock_cycle(clock))
+        .collect();
+    let sensors = &body.sensors;
+
+    for (index, muscle) in body.muscles.iter_mut().enumerate() {
+        let angle = (cycles[muscle.clock] + muscle.phase) * TAU;
+        let inputs: Vec<f32> = [angle.sin(), angle.cos()]
+            .into_iter()
+            .chain(muscle_inputs(muscle, index, sensors))
+            .collect();
+
+        let contraction = sigmoid(activate(network, &order, &inputs));
+        set_contraction(muscle, contraction);
+    }
+}
+
+/// Inputs every controller gets from the sensors of a muscle: its length relative
+/// to its range, whether each of its nodes touches the ground, and a bias
+fn muscle_inputs(muscle: &BodyMuscle, index: usize, sensors: &Sensors) -> [f32; 4] {
+    let range = muscle.extended_length - muscle.contracted_length;
+    let relative_length = if range.abs() > f32::EPSILON {
+        (sensors.muscles[index].length - muscle.contracted_length) / range
+    } else {
+        0.0
+    };
+    let contact = |node: usize| {
+        if sensors.nodes[node].ground_contact {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    [
+        relative_length,
+        contact(muscle.nodes.0),
+        contact(muscle.nodes.1),
+        1.0,
+    ]
+}
+
+/// 1 is fully contracted, 0 fully extended
+fn set_contraction(muscle: &mut BodyMuscle, contraction: f32) {
+    muscle.controlled_length =
+        muscle.contracted_length * contraction + muscle.extended_length * (1.0 - contraction);
+}
+
+/// Hidden and output neurons, each one after every neuron that feeds it.
+/// Neurons in a cycle are left out, they stay silent
+fn evaluation_order(network: &NeatPhenotype) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::new();
+    let mut pending: Vec<usize> = network
+        .neurons
+        .iter()
+        .filter(|neuron| neuron.kind != NeuronKind::Input)
+        .map(|neuron| neuron.id)
+        .collect();
+    let is_ready = |neuron: usize, order: &[usize]| {
+        network.connections.iter().all(|connection| {
+            connection.to != neuron
+                || !connection.enabled
+                || connection.from < NEAT_OUTPUT
+                || order.contains(&connection.from)
+        })
+    };
+
+    while let Some(position) = pending.iter().position(|&neuron| is_ready(neuron, &order)) {
+        order.push(pending.remove(position));
+    }
+
+    order
+}
+
+/// Value of the output of the network, before the activation
+fn activate(network: &NeatPhenotype, order: &[usize], inputs: &[f32]) -> f32 {
+    let mut values: HashMap<usize, f32> = inputs.iter().copied().enumerate().collect();
+    let mut output = 0.0;
+
+    for &neuron in order {
+        let sum: f32 = network
+            .connections
+            .iter()
+            .filter(|connection| connection.enabled && connection.to == neuron)
+            .map(|connection| {
+                connection.weight * values.get(&connection.from).copied().unwrap_or(0.0)
+            })
+            .sum();
+
+        if neuron == NEAT_OUTPUT {
+            output = sum;
+        } else {
+            values.insert(neuron, sum.tanh());
+        }
+    }
+
+    output
+}
+
+/// Missing weights count as zero
+fn weighted_sum(weights: &[f32], inputs: &[f32]) -> f32 {
+    weights
+        .iter()
+        .zip(inputs)
+        .map(|(weight, input)| weight * input)
+        .sum()
+}
+
+fn sigmoid(value: f32) -> f32 {
+    1.0 / (1.0 + (-value).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{TerrainProfile, CONFIG},
+        genetic_algorithm::{
+            controller_phenotype::ControllerPhenotype, creature_chromosome::CreatureChromosome,
+            muscle_phenotype::MusclePhenotype, node_phenotype::NodePhenotype,
+        },
+    };
+    use crate::{
+        genetic_algorithm::{
+            neat_phenotype::{ConnectionGene, NeuronGene, NEAT_INPUTS},
+            operations::RandomCreatable,
+        },
+        simulation2d::world::{sensors::read_sensors, terrain::Terrain},
+    };
+    use bevy::math::Vec2;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn body(controller_weights: Vec<f32>) -> Body {
+        let chromosome = CreatureChromosome {
+            internal_clock_size: 1.0,
+            nodes: vec![
+                NodePhenotype {
+                    position: Vec2::new(0.0, 0.0),
+                    ..Default::default()
+                },
+                NodePhenotype {
+                    position: Vec2::new(0.2, 0.0),
+                    ..Default::default()
+                },
+            ],
+            muscles: vec![MusclePhenotype {
+                contracted_length: 0.1,
+                extended_length: 0.3,
+                controller_weights,
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            controller: ControllerPhenotype {
+                hidden_weights: vec![vec![0.0, 10.0, 0.0]],
+            },
+            ..Default::default()
+        };
+
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+        body.sensors = read_sensors(&body, &Terrain::new(&TerrainProfile::Flat), &[]);
+        body
+    }
+
+    #[test]
+    fn test_silent_network_holds_muscles_halfway() {
+        let mut body = body(Vec::new());
+
+        update_controlled_lengths(&mut body);
+
+        assert!((body.muscles[0].controlled_length - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_network_follows_the_clock() {
+        let mut body = body(vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 10.0]);
+
+        update_controlled_lengths(&mut body);
+        let start = body.muscles[0].controlled_length;
+        body.clocks[0] = 0.5;
+        update_controlled_lengths(&mut body);
+        let half = body.muscles[0].controlled_length;
+
+        assert!((start - 0.1).abs() < 1e-3, "start {}", start);
+        assert!((half - 0.3).abs() < 1e-3, "half {}", half);
+    }
+
+    #[test]
+    fn test_network_reads_the_clock_of_the_muscle() {
+        let mut body = body(vec![0.0, 0.0, 0.0, 0.0, 0.0, -10.0]);
+        body.clocks = vec![0.0, 0.5];
+        body.clock_sizes = vec![1.0, 1.0];
+
+        update_controlled_lengths(&mut body);
+        let internal = body.muscles[0].controlled_length;
+        body.muscles[0].clock = 1;
+        update_controlled_lengths(&mut body);
+        let own = body.muscles[0].controlled_length;
+
+        assert!((internal - 0.3).abs() < 1e-3, "internal {}", internal);
+        assert!((own - 0.1).abs() < 1e-3, "own {}", own);
+    }
+
+    #[test]
+    fn test_neat_network_runs_hidden_neurons_first() {
+        let mut body = body(Vec::new());
+        let connection = |from: usize, to: usize, weight: f32, enabled: bool| ConnectionGene {
+            innovation: 0,
+            from,
+            to,
+            weight,
+            enabled,
+        };
+        let mut network = NeatPhenotype::random(&mut StdRng::seed_from_u64(0));
+        network.neurons.push(NeuronGene {
+            id: NEAT_OUTPUT + 1,
+            kind: NeuronKind::Hidden,
+        });
+        network.connections = vec![
+            connection(NEAT_OUTPUT + 1, NEAT_OUTPUT, 2.0, true),
+            connection(NEAT_INPUTS - 1, NEAT_OUTPUT + 1, 1.0, true),
+            connection(NEAT_INPUTS - 1, NEAT_OUTPUT, 5.0, false),
+        ];
+        body.chromosome.neat_controller = network;
+
+        update_neat_lengths(&mut body);
+
+        let contraction = sigmoid(2.0 * 1.0_f32.tanh());
+        let expected = 0.1 * contraction + 0.3 * (1.0 - contraction);
+        assert!((body.muscles[0].controlled_length - expected).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,224 @@
+use bevy::math::Vec2;
+
+use crate::config::Integrator;
+
+use super::{
+    body::{Body, BodyNode},
+    physics::{accelerations, body_accelerations, friction_accelerations},
+    WorldSettings,
+};
+
+/// Advances the positions and velocities of the nodes of a body
+/// with the integrator chosen in the settings
+pub fn integrate(body: &mut Body, settings: &WorldSettings, delta_time: f32) {
+    body.nodes = match settings.integrator {
+        Integrator::SemiImplicitEuler => semi_implicit_euler(body, settings, delta_time),
+        Integrator::VelocityVerlet => velocity_verlet(body, settings, delta_time),
+        Integrator::RungeKutta4 => runge_kutta_4(body, settings, delta_time),
+    };
+}
+
+fn with_state(node: &BodyNode, position: Vec2, velocity: Vec2) -> BodyNode {
+    BodyNode {
+        position,
+        velocity,
+        ..node.clone()
+    }
+}
+
+/// Friction is evaluated with the velocity already changed by the other forces,
+/// so it slows down the motion the node is about to have
+fn semi_implicit_euler(body: &Body, settings: &WorldSettings, delta_time: f32) -> Vec<BodyNode> {
+    let accelerated: Vec<BodyNode> = body
+        .nodes
+        .iter()
+        .zip(body_accelerations(body, &body.nodes, settings))
+        .map(|(node, acceleration)| {
+            with_state(
+                node,
+                node.position,
+                node.velocity + acceleration * delta_time,
+            )
+        })
+        .collect();
+
+    accelerated
+        .iter()
+        .zip(friction_accelerations(&accelerated, settings))
+        .map(|(node, friction)| {
+            let velocity = node.velocity + friction * delta_time;
+            with_state(node, node.position + velocity * delta_time, velocity)
+        })
+        .collect()
+}
+
+/// Forces that depend on velocity, like friction, are evaluated
+/// at the end of the step with a velocity predicted by Euler
+fn velocity_verlet(body: &Body, settings: &WorldSettings, delta_time: f32) -> Vec<BodyNode> {
+    let start_accelerations = accelerations(body, &body.nodes, settings);
+
+    let predicted: Vec<BodyNode> = body
+        .nodes
+        .iter()
+        .zip(start_accelerations.iter())
+        .map(|(node, &acceleration)| {
+            with_state(
+                node,
+                node.position
+                    + node.velocity * delta_time
+                    + acceleration * (0.5 * delta_time * delta_time),
+                node.velocity + acceleration * delta_time,
+            )
+        })
+        .collect();
+
+    let end_accelerations = accelerations(body, &predicted, settings);
+
+    body.nodes
+        .iter()
+        .zip(predicted.iter())
+        .zip(start_accelerations.iter().zip(end_accelerations))
+        .map(|((node, predicted), (&start, end))| {
+            with_state(
+                node,
+                predicted.position,
+                node.velocity + (start + end) * (0.5 * delta_time),
+            )
+        })
+        .collect()
+}
+
+/// Rate of change of the position and velocity of every node
+fn derivatives(body: &Body, nodes: &[BodyNode], settings: &WorldSettings) -> Vec<(Vec2, Vec2)> {
+    nodes
+        .iter()
+        .map(|node| node.velocity)
+        .zip(accelerations(body, nodes, settings))
+        .collect()
+}
+
+/// State of the nodes after following the derivatives for some time
+fn advance(nodes: &[BodyNode], derivatives: &[(Vec2, Vec2)], time: f32) -> Vec<BodyNode> {
+    nodes
+        .iter()
+        .zip(derivatives.iter())
+        .map(|(node, &(velocity, acceleration))| {
+            with_state(
+                node,
+                node.position + velocity * time,
+                node.velocity + acceleration * time,
+            )
+        })
+        .collect()
+}
+
+fn runge_kutta_4(body: &Body, settings: &WorldSettings, delta_time: f32) -> Vec<BodyNode> {
+    let nodes = &body.nodes;
+    let half_step = 0.5 * delta_time;
+
+    let first = derivatives(body, nodes, settings);
+    let second = derivatives(body, &advance(nodes, &first, half_step), settings);
+    let third = derivatives(body, &advance(nodes, &second, half_step), settings);
+    let fourth = derivatives(body, &advance(nodes, &third, delta_time), settings);
+
+    let combined: Vec<(Vec2, Vec2)> = (0..nodes.len())
+        .map(|index| {
+            let position_rate =
+                (first[index].0 + second[index].0 * 2.0 + third[index].0 * 2.0 + fourth[index].0)
+                    / 6.0;
+            let velocity_rate =
+                (first[index].1 + second[index].1 * 2.0 + third[index].1 * 2.0 + fourth[index].1)
+                    / 6.0;
+
+            (position_rate, velocity_rate)
+        })
+        .collect();
+
+    advance(nodes, &combined, delta_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::CONFIG,
+        genetic_algorithm::{
+            creature_chromosome::CreatureChromosome, muscle_phenotype::MusclePhenotype,
+            node_phenotype::NodePhenotype,
+        },
+    };
+
+    /// A stiff muscle far from the ground, so only the muscle moves the nodes
+    fn stiff_body() -> Body {
+        let chromosome = CreatureChromosome {
+            internal_clock_size: 100.0,
+            nodes: vec![
+                NodePhenotype {
+                    position: Vec2::new(0.0, 10.0),
+                    friction: 0.5,
+                    mass: 1.0,
+                },
+                NodePhenotype {
+                    position: Vec2::new(0.4, 10.0),
+                    friction: 0.5,
+                    mass: 1.0,
+                },
+            ],
+            muscles: vec![MusclePhenotype {
+                contracted_time: 1.0,
+                extended_length: 0.4,
+                contracted_length: 0.1,
+                strength: 200.0,
+                damping: 0.5,
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        Body::new(&chromosome, CONFIG.node_size)
+    }
+
+    fn simulate(integrator: Integrator, delta_time: f32, duration: f32) -> Vec<Vec2> {
+        let mut settings = WorldSettings::new(&CONFIG.instances[0]);
+        settings.gravity = 0.0;
+        settings.integrator = integrator;
+
+        let mut body = stiff_body();
+        let steps = (duration / delta_time).round() as usize;
+        for _ in 0..steps {
+            integrate(&mut body, &settings, delta_time);
+        }
+
+        body.nodes.iter().map(|node| node.position).collect()
+    }
+
+    fn error(positions: &[Vec2], reference: &[Vec2]) -> f32 {
+        positions
+            .iter()
+            .zip(reference.iter())
+            .map(|(position, reference)| (*position - *reference).length())
+            .sum()
+    }
+
+    #[test]
+    fn test_higher_order_integrators_are_more_accurate() {
+        let reference = simulate(Integrator::RungeKutta4, 0.0005, 1.0);
+
+        let euler = error(
+            &simulate(Integrator::SemiImplicitEuler, 0.05, 1.0),
+            &reference,
+        );
+        let verlet = error(&simulate(Integrator::VelocityVerlet, 0.05, 1.0), &reference);
+        let runge_kutta = error(&simulate(Integrator::RungeKutta4, 0.05, 1.0), &reference);
+
+        assert!(euler.is_finite() && verlet.is_finite() && runge_kutta.is_finite());
+        assert!(verlet < euler, "Verlet {} >= Euler {}", verlet, euler);
+        assert!(
+            runge_kutta < verlet,
+            "RK4 {} >= Verlet {}",
+            runge_kutta,
+            verlet
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::math::Vec2;
+
+use crate::config::Medium;
+
+use super::{
+    body::{Body, BodyNode},
+    WorldSettings,
+};
+
+/// Adds the drag of every muscle and bone and the buoyancy of every node,
+/// nothing happens in the air
+pub fn apply_medium_forces(
+    body: &Body,
+    nodes: &[BodyNode],
+    settings: &WorldSettings,
+    forces: &mut [Vec2],
+) {
+    if let Medium::Water {
+        buoyancy,
+        normal_drag,
+        tangential_drag,
+    } = settings.medium
+    {
+        apply_buoyancy(buoyancy, settings, forces);
+
+        let segments = body
+            .muscles
+            .iter()
+            .map(|muscle| muscle.nodes)
+            .chain(body.bones.iter().map(|bone| bone.nodes));
+
+        for segment in segments {
+            apply_drag(segment, nodes, normal_drag, tangential_drag, forces);
+        }
+    }
+}
+
+/// Pushes nodes up, a buoyancy of 1 cancels the weight of a node of mass 1
+fn apply_buoyancy(buoyancy: f32, settings: &WorldSettings, forces: &mut [Vec2]) {
+    let lift = Vec2::new(0.0, settings.gravity) * buoyancy;
+
+    for force in forces.iter_mut() {
+        *force += lift;
+    }
+}
+
+/// Opposes the motion of the middle of a segment, split between its two nodes.
+/// Longer segments are dragged more
+fn apply_drag(
+    (first, second): (usize, usize),
+    nodes: &[BodyNode],
+    normal_drag: f32,
+    tangential_drag: f32,
+    forces: &mut [Vec2],
+) {
+    let offset = nodes[second].position - nodes[first].position;
+    let length = offset.length();
+    if length == 0.0 {
+        return;
+    }
+
+    let tangent = offset / length;
+    let velocity = (nodes[first].velocity + nodes[second].velocity) * 0.5;
+    let tangential_velocity = tangent * velocity.dot(tangent);
+    let normal_velocity = velocity - tangential_velocity;
+
+    let drag = -(normal_velocity * normal_drag + tangential_velocity * tangential_drag) * length;
+
+    forces[first] += drag * 0.5;
+    forces[second] += drag * 0.5;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::CONFIG, simulation2d::world::body::BodyMuscle};
+
+    fn moving_segment(velocity: Vec2) -> Body {
+        let node = |x: f32| BodyNode {
+            position: Vec2::new(x, 1.0),
+            velocity,
+            mass: 1.0,
+            ..Default::default()
+        };
+
+        Body {
+            nodes: vec![node(0.0), node(1.0)],
+            muscles: vec![BodyMuscle {
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_segments_are_dragged_more_across_than_along() {
+        let mut settings = WorldSettings::new(&CONFIG.instances[0]);
+        settings.medium = Medium::Water {
+            buoyancy: 0.0,
+            normal_drag: 5.0,
+            tangential_drag: 0.5,
+        };
+
+        let along = moving_segment(Vec2::new(1.0, 0.0));
+        let mut along_forces = vec![Vec2::ZERO; 2];
+        apply_medium_forces(&along, &along.nodes, &settings, &mut along_forces);
+
+        let across = moving_segment(Vec2::new(0.0, 1.0));
+        let mut across_forces = vec![Vec2::ZERO; 2];
+        apply_medium_forces(&across, &across.nodes, &settings, &mut across_forces);
+
+        assert_eq!(along_forces, vec![Vec2::new(-0.25, 0.0); 2]);
+        assert_eq!(across_forces, vec![Vec2::new(0.0, -2.5); 2]);
+    }
+}
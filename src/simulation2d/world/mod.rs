@@ -0,0 +1,338 @@
+pub mod body;
+pub mod bone;
+pub mod collision;
+pub mod controller;
+pub mod integrator;
+pub mod medium;
+pub mod obstacle;
+pub mod physics;
+pub mod sensors;
+pub mod terrain;
+
+use bevy::math::Vec2;
+
+use crate::{
+    config::{
+        FitnessFunction, Instance, Integrator, Medium, MuscleActuation, MuscleModel,
+        NodeCollisions, Obstacle, CONFIG,
+    },
+    genetic_algorithm::{behaviour::Behaviour, creature_chromosome::CreatureChromosome},
+};
+
+use body::{Body, Failure};
+use terrain::Terrain;
+
+/// Parameters of the simulated world
+#[derive(Debug, Clone)]
+pub struct WorldSettings {
+    pub gravity: f32,
+    pub air_friction: f32,
+    pub node_collisions: NodeCollisions,
+    pub terrain: Terrain,
+    pub obstacles: Vec<Obstacle>,
+    pub medium: Medium,
+    /// Simulated seconds advanced by every step
+    pub fixed_time_step: f32,
+    /// Simulated seconds a creature is evaluated for
+    pub evaluation_time: f32,
+    pub node_size: f32,
+    /// Bodies with a node faster than this fail
+    pub max_node_speed: f32,
+    pub integrator: Integrator,
+    pub muscle_model: MuscleModel,
+    pub muscle_actuation: MuscleActuation,
+    pub fitness_function: FitnessFunction,
+}
+
+impl WorldSettings {
+    /// Creates the settings for an instance, the rest is taken from the config
+    pub fn new(instance: &Instance) -> WorldSettings {
+        WorldSettings {
+            gravity: instance.gravity,
+            air_friction: instance.air_friction,
+            node_collisions: instance.node_collisions,
+            terrain: Terrain::new(&instance.terrain),
+            obstacles: instance.obstacles.clone(),
+            medium: instance.medium,
+            fixed_time_step: CONFIG.fixed_time_step,
+            evaluation_time: CONFIG.evaluation_time,
+            node_size: CONFIG.node_size,
+            max_node_speed: CONFIG.max_node_speed,
+            integrator: CONFIG.integrator,
+            muscle_model: CONFIG.muscle_model,
+            muscle_actuation: CONFIG.muscle_actuation,
+            fitness_function: CONFIG.fitness_function,
+        }
+    }
+}
+
+/// Result of simulating a single creature for a whole evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub fitness: f32,
+    /// Work done by the muscles of the creature
+    pub energy: f32,
+    /// Why the evaluation ended early, if it did
+    pub failure: Option<Failure>,
+    pub behaviour: Behaviour,
+    /// Position of the creature after every step
+    pub trajectory: Vec<Vec2>,
+}
+
+/// 2D world where creatures are simulated, stepped deterministically
+/// with a fixed time step
+#[derive(Debug, Clone)]
+pub struct World2d {
+    settings: WorldSettings,
+    bodies: Vec<Body>,
+    steps: usize,
+}
+
+impl World2d {
+    pub fn new(settings: WorldSettings) -> World2d {
+        World2d {
+            settings,
+            bodies: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Simulates a creature on its own for a whole evaluation
+    pub fn evaluate(chromosome: &CreatureChromosome, settings: WorldSettings) -> Evaluation {
+        let mut world = World2d::new(settings);
+        let index = world.add_creature(chromosome);
+        let mut trajectory = Vec::with_capacity(world.evaluation_steps());
+
+        while world.is_evaluating() {
+            world.step();
+            trajectory.push(world.bodies[index].position());
+        }
+
+        Evaluation {
+            fitness: world.fitness(index),
+            energy: world.bodies[index].energy(),
+            failure: world.bodies[index].failure,
+            behaviour: world.bodies[index].behaviour(),
+            trajectory,
+        }
+    }
+
+    pub fn settings(&self) -> &WorldSettings {
+        &self.settings
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    /// Adds a creature to the world standing on the ground at the origin,
+    /// returns the index of its body
+    pub fn add_creature(&mut self, chromosome: &CreatureChromosome) -> usize {
+        let mut body = Body::new(chromosome, self.settings.node_size);
+        let ground_height =
+            obstacle::ground_height(&self.settings.terrain, &self.settings.obstacles, 0.0);
+
+        for node in body.nodes.iter_mut() {
+            node.position.y += ground_height;
+        }
+        body.sensors =
+            sensors::read_sensors(&body, &self.settings.terrain, &self.settings.obstacles);
+
+        self.bodies.push(body);
+        self.bodies.len() - 1
+    }
+
+    /// Fitness of a body with the fitness function of the world
+    pub fn fitness(&self, body_index: usize) -> f32 {
+        self.bodies[body_index].fitness(self.settings.fitness_function)
+    }
+
+    /// Removes every creature and resets the time
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+        self.steps = 0;
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        self.steps as f32 * self.settings.fixed_time_step
+    }
+
+    /// Amount of steps an evaluation takes
+    pub fn evaluation_steps(&self) -> usize {
+        (self.settings.evaluation_time / self.settings.fixed_time_step).round() as usize
+    }
+
+    /// The evaluation is over when its time is up or every body failed
+    pub fn is_finished(&self) -> bool {
+        self.steps >= self.evaluation_steps()
+            || self.bodies.iter().all(|body| body.failure.is_some())
+    }
+
+    /// Whether there are creatures left to step
+    pub fn is_evaluating(&self) -> bool {
+        !self.bodies.is_empty() && !self.is_finished()
+    }
+
+    /// Advances the world by a fixed time step
+    pub fn step(&mut self) {
+        let delta_time = self.settings.fixed_time_step;
+
+        for body in self.bodies.iter_mut() {
+            if body.failure.is_some() {
+                continue;
+            }
+
+            physics::advance_clocks(body, delta_time);
+            physics::advance_reflexes(body, delta_time);
+            match self.settings.muscle_actuation {
+                MuscleActuation::NeuralNetwork => controller::update_controlled_lengths(body),
+                MuscleActuation::Neat => controller::update_neat_lengths(body),
+                MuscleActuation::DutyCycle | MuscleActuation::Oscillator => {}
+            }
+
+            let muscle_forces: Vec<f32> = body
+                .muscles
+                .iter()
+                .map(|muscle| physics::muscle_force(body, muscle, &body.nodes, &self.settings))
+                .collect();
+            let muscle_lengths = body.muscle_lengths();
+
+            integrator::integrate(body, &self.settings, delta_time);
+            bone::solve_bones(body);
+
+            physics::spend_energy(body, &muscle_forces, &muscle_lengths);
+
+            if self.settings.node_collisions != NodeCollisions::Disabled {
+                collision::resolve_node_collisions(body);
+            }
+        }
+
+        if self.settings.node_collisions == NodeCollisions::AllCreatures {
+            collision::resolve_body_collisions(&mut self.bodies);
+        }
+
+        for body in self.bodies.iter_mut() {
+            if body.failure.is_some() {
+                continue;
+            }
+
+            obstacle::resolve_obstacle_collisions(
+                body,
+                &self.settings.obstacles,
+                &self.settings.terrain,
+                delta_time,
+            );
+            physics::constrain_to_ground(body, &self.settings.terrain, &self.settings.obstacles);
+
+            body.failure = physics::check_failure(body, self.settings.max_node_speed);
+
+            let sensors =
+                sensors::read_sensors(body, &self.settings.terrain, &self.settings.obstacles);
+            physics::trigger_reflexes(body, &sensors::touchdowns(&body.sensors, &sensors));
+            body.sensors = sensors;
+            body.record_behaviour(delta_time);
+        }
+
+        self.steps += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genetic_algorithm::{
+        muscle_phenotype::MusclePhenotype, node_phenotype::NodePhenotype,
+    };
+
+    fn settings() -> WorldSettings {
+        WorldSettings::new(&CONFIG.instances[0])
+    }
+
+    fn chromosome() -> CreatureChromosome {
+        CreatureChromosome {
+            internal_clock_size: 0.5,
+            nodes: vec![
+                NodePhenotype {
+                    position: Vec2::new(-0.1, 0.1),
+                    friction: 0.3,
+                    mass: 1.0,
+                },
+                NodePhenotype {
+                    position: Vec2::new(0.1, 0.1),
+                    friction: 1.0,
+                    mass: 1.0,
+                },
+            ],
+            muscles: vec![MusclePhenotype {
+                contracted_time: 0.3,
+                extended_length: 0.3,
+                contracted_length: 0.1,
+                strength: 5.0,
+                damping: 0.5,
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluation_is_deterministic() {
+        let first = World2d::evaluate(&chromosome(), settings());
+        let second = World2d::evaluate(&chromosome(), settings());
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_evaluation_lasts_evaluation_time() {
+        let settings = settings();
+        let expected_steps = (settings.evaluation_time / settings.fixed_time_step).round() as usize;
+
+        let evaluation = World2d::evaluate(&chromosome(), settings);
+
+        assert_eq!(evaluation.trajectory.len(), expected_steps);
+    }
+
+    #[test]
+    fn test_efficient_fitness_divides_distance_by_energy() {
+        let distance = World2d::evaluate(&chromosome(), settings());
+
+        let mut settings = settings();
+        settings.fitness_function = FitnessFunction::DistancePerEnergy;
+        let efficiency = World2d::evaluate(&chromosome(), settings);
+
+        assert!(distance.energy > 0.0);
+        assert!((efficiency.fitness * (1.0 + distance.energy) - distance.fitness).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_nodes_stay_above_ground() {
+        let mut world = World2d::new(settings());
+        world.add_creature(&chromosome());
+
+        while world.is_evaluating() {
+            world.step();
+            assert!(world.bodies()[0]
+                .nodes
+                .iter()
+                .all(|node| node.position.y >= 0.0));
+        }
+    }
+
+    #[test]
+    fn test_nodes_stay_above_sloped_terrain() {
+        let mut settings = settings();
+        settings.terrain = Terrain::Slope { slope: 0.5 };
+        let mut world = World2d::new(settings);
+        world.add_creature(&chromosome());
+
+        while world.is_evaluating() {
+            world.step();
+            assert!(world.bodies()[0]
+                .nodes
+                .iter()
+                .all(|node| node.position.y >= 0.5 * node.position.x - 1e-5));
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use bevy::math::Vec2;
+
+use crate::config::Obstacle;
+
+use super::{
+    body::{Body, BodyNode},
+    terrain::Terrain,
+};
+
+/// Height of the ground at x, the terrain with the pits dug into it
+pub fn ground_height(terrain: &Terrain, obstacles: &[Obstacle], x: f32) -> f32 {
+    let pit_depth = obstacles
+        .iter()
+        .map(|obstacle| match *obstacle {
+            Obstacle::Pit {
+                start,
+                width,
+                depth,
+            } if x >= start && x <= start + width => depth,
+            _ => 0.0,
+        })
+        .fold(0.0, f32::max);
+
+    terrain.height(x) - pit_depth
+}
+
+/// Nodes pushed out through a step riser are left this far from it, relative
+/// to the width of the steps, so they stay on the lower step
+const RISER_GAP: f32 = 1e-4;
+
+/// Pushes nodes out of boxes, pit walls and step risers through the closest face.
+/// Nodes lose their velocity into the face and slide on it with friction
+pub fn resolve_obstacle_collisions(
+    body: &mut Body,
+    obstacles: &[Obstacle],
+    terrain: &Terrain,
+    delta_time: f32,
+) {
+    for node in body.nodes.iter_mut() {
+        if let Terrain::Steps { height, width } = *terrain {
+            resolve_step_risers(node, height, width, delta_time);
+        }
+
+        for obstacle in obstacles.iter() {
+            match *obstacle {
+                Obstacle::Box { min, max } => resolve_box(node, min, max, delta_time),
+                Obstacle::Pit { start, width, .. } => {
+                    resolve_pit_walls(node, start, start + width, terrain, delta_time)
+                }
+            }
+        }
+    }
+}
+
+fn resolve_box(node: &mut BodyNode, min: Vec2, max: Vec2, delta_time: f32) {
+    let position = node.position;
+
+    if position.x <= min.x || position.x >= max.x || position.y <= min.y || position.y >= max.y {
+        return;
+    }
+
+    let exits = [
+        (position.x - min.x, Vec2::new(-1.0, 0.0)),
+        (max.x - position.x, Vec2::new(1.0, 0.0)),
+        (position.y - min.y, Vec2::new(0.0, -1.0)),
+        (max.y - position.y, Vec2::new(0.0, 1.0)),
+    ];
+
+    let (depth, normal) =
+        exits.iter().copied().fold(
+            exits[0],
+            |closest, exit| {
+                if exit.0 < closest.0 {
+                    exit
+                } else {
+                    closest
+                }
+            },
+        );
+
+    node.position += normal * depth;
+    stop_against(node, normal, delta_time);
+}
+
+/// A node under the ground next to a pit went through its wall
+/// if it is closer to the wall than to the surface
+fn resolve_pit_walls(
+    node: &mut BodyNode,
+    start: f32,
+    end: f32,
+    terrain: &Terrain,
+    delta_time: f32,
+) {
+    let position = node.position;
+    let depth_below_surface = terrain.height(position.x) - position.y;
+
+    if depth_below_surface <= 0.0 {
+        return;
+    }
+
+    if position.x < start && start - position.x < depth_below_surface {
+        node.position.x = start;
+        stop_against(node, Vec2::new(1.0, 0.0), delta_time);
+    } else if position.x > end && position.x - end < depth_below_surface {
+        node.position.x = end;
+        stop_against(node, Vec2::new(-1.0, 0.0), delta_time);
+    }
+}
+
+/// A node under a step went through its riser, the wall on the side of
+/// the lower step, if it is closer to the riser than to the top of the step
+fn resolve_step_risers(node: &mut BodyNode, height: f32, width: f32, delta_time: f32) {
+    let position = node.position;
+    let step = (position.x / width).floor();
+    let depth_below_surface = step * height - position.y;
+
+    if depth_below_surface <= 0.0 {
+        return;
+    }
+
+    let (riser, normal) = if height > 0.0 {
+        (step * width, Vec2::new(-1.0, 0.0))
+    } else {
+        ((step + 1.0) * width, Vec2::new(1.0, 0.0))
+    };
+
+    if (position.x - riser).abs() < depth_below_surface {
+        node.position.x = riser + normal.x * RISER_GAP * width;
+        stop_against(node, normal, delta_time);
+    }
+}
+
+fn stop_against(node: &mut BodyNode, normal: Vec2, delta_time: f32) {
+    let normal_speed = node.velocity.dot(normal);
+    if normal_speed < 0.0 {
+        node.velocity -= normal * normal_speed;
+    }
+
+    let tangent = normal.perp();
+    node.velocity -= tangent * node.velocity.dot(tangent) * node.friction * delta_time;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body_with_node(position: Vec2, velocity: Vec2) -> Body {
+        Body {
+            nodes: vec![BodyNode {
+                position,
+                velocity,
+                radius: 0.05,
+                friction: 0.0,
+                mass: 1.0,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_node_is_pushed_out_of_box_through_closest_face() {
+        let obstacles = [Obstacle::Box {
+            min: Vec2::new(1.0, 0.0),
+            max: Vec2::new(2.0, 0.5),
+        }];
+        let mut body = body_with_node(Vec2::new(1.1, 0.2), Vec2::new(1.0, 0.5));
+
+        resolve_obstacle_collisions(&mut body, &obstacles, &Terrain::Flat, 0.01);
+
+        assert_eq!(body.nodes[0].position, Vec2::new(1.0, 0.2));
+        assert_eq!(body.nodes[0].velocity, Vec2::new(0.0, 0.5));
+    }
+
+    #[test]
+    fn test_pit_lowers_ground_and_has_walls() {
+        let obstacles = [Obstacle::Pit {
+            start: 1.0,
+            width: 1.0,
+            depth: 0.5,
+        }];
+        assert!((ground_height(&Terrain::Flat, &obstacles, 1.5) + 0.5).abs() < f32::EPSILON);
+        assert!(ground_height(&Terrain::Flat, &obstacles, 2.5).abs() < f32::EPSILON);
+
+        let mut body = body_with_node(Vec2::new(2.1, -0.4), Vec2::new(1.0, 0.0));
+
+        resolve_obstacle_collisions(&mut body, &obstacles, &Terrain::Flat, 0.01);
+
+        assert_eq!(body.nodes[0].position, Vec2::new(2.0, -0.4));
+        assert_eq!(body.nodes[0].velocity, Vec2::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_step_risers_are_walls() {
+        let terrain = Terrain::Steps {
+            height: 0.2,
+            width: 1.0,
+        };
+        let mut body = body_with_node(Vec2::new(1.02, 0.05), Vec2::new(1.0, -0.5));
+
+        resolve_obstacle_collisions(&mut body, &[], &terrain, 0.01);
+
+        let node = &body.nodes[0];
+        assert!(node.position.x < 1.0 && node.position.x > 0.99);
+        assert!((node.position.y - 0.05).abs() < f32::EPSILON);
+        assert_eq!(node.velocity, Vec2::new(0.0, -0.5));
+        assert!(terrain.height(node.position.x).abs() < f32::EPSILON);
+
+        // Nodes well inside the step come out through its top
+        let mut body = body_with_node(Vec2::new(1.5, 0.15), Vec2::ZERO);
+        resolve_obstacle_collisions(&mut body, &[], &terrain, 0.01);
+        assert_eq!(body.nodes[0].position, Vec2::new(1.5, 0.15));
+    }
+}
@@ -0,0 +1,429 @@
+use bevy::math::Vec2;
+
+use crate::{
+    config::{MuscleActuation, MuscleModel, Obstacle},
+    genetic_algorithm::muscle_phenotype::Trigger,
+};
+
+use super::{
+    body::{Body, BodyMuscle, BodyNode, Failure},
+    medium::apply_medium_forces,
+    obstacle::ground_height,
+    sensors::touches_ground,
+    terrain::Terrain,
+    WorldSettings,
+};
+
+pub fn advance_clocks(body: &mut Body, delta_time: f32) {
+    for (clock, clock_size) in body.clocks.iter_mut().zip(body.clock_sizes.iter()) {
+        *clock += delta_time;
+
+        if *clock >= *clock_size {
+            *clock = 0.0;
+        }
+    }
+}
+
+/// Starts the contraction of every muscle triggered by one of the nodes that touched down
+pub fn trigger_reflexes(body: &mut Body, touchdowns: &[usize]) {
+    for muscle in body.muscles.iter_mut() {
+        if let Trigger::GroundContact { node } = muscle.trigger {
+            if touchdowns.contains(&node) {
+                muscle.reflex_time = muscle.contracted_time * body.clock_sizes[muscle.clock];
+            }
+        }
+    }
+}
+
+pub fn advance_reflexes(body: &mut Body, delta_time: f32) {
+    for muscle in body.muscles.iter_mut() {
+        muscle.reflex_time = (muscle.reflex_time - delta_time).max(0.0);
+    }
+}
+
+/// Acceleration of every node of a body when its nodes are in the given state.
+/// Every integrator evaluates the same forces through it
+pub fn accelerations(body: &Body, nodes: &[BodyNode], settings: &WorldSettings) -> Vec<Vec2> {
+    body_accelerations(body, nodes, settings)
+        .into_iter()
+        .zip(friction_accelerations(nodes, settings))
+        .map(|(body_acceleration, friction)| body_acceleration + friction)
+        .collect()
+}
+
+/// Acceleration of every node caused by muscles, gravity and the medium, without ground friction
+pub fn body_accelerations(body: &Body, nodes: &[BodyNode], settings: &WorldSettings) -> Vec<Vec2> {
+    let mut forces = vec![Vec2::ZERO; nodes.len()];
+
+    apply_muscle_forces(body, nodes, settings, &mut forces);
+    apply_gravity(nodes, settings.gravity, &mut forces);
+    apply_medium_forces(body, nodes, settings, &mut forces);
+
+    divide_by_mass(nodes, forces)
+}
+
+/// Acceleration of every node caused by ground friction
+pub fn friction_accelerations(nodes: &[BodyNode], settings: &WorldSettings) -> Vec<Vec2> {
+    let mut forces = vec![Vec2::ZERO; nodes.len()];
+    apply_friction(nodes, &settings.terrain, &settings.obstacles, &mut forces);
+
+    divide_by_mass(nodes, forces)
+}
+
+fn divide_by_mass(nodes: &[BodyNode], forces: Vec<Vec2>) -> Vec<Vec2> {
+    nodes
+        .iter()
+        .zip(forces)
+        .map(|(node, force)| force / node.mass)
+        .collect()
+}
+
+fn apply_muscle_forces(
+    body: &Body,
+    nodes: &[BodyNode],
+    settings: &WorldSettings,
+    forces: &mut [Vec2],
+) {
+    for muscle in body.muscles.iter() {
+        let force = muscle_force(body, muscle, nodes, settings);
+        let first_to_second_direction = muscle_direction(muscle, nodes);
+
+        forces[muscle.nodes.0] -= first_to_second_direction * force;
+        forces[muscle.nodes.1] += first_to_second_direction * force;
+    }
+}
+
+/// Force a muscle makes along itself when its nodes are in the given state.
+/// Positive forces push the nodes apart, negative ones pull them together
+pub fn muscle_force(
+    body: &Body,
+    muscle: &BodyMuscle,
+    nodes: &[BodyNode],
+    settings: &WorldSettings,
+) -> f32 {
+    let target_length = target_length(body, muscle, settings.muscle_actuation);
+    let muscle_length = (nodes[muscle.nodes.1].position - nodes[muscle.nodes.0].position).length();
+    let strength = muscle.strength * (1.0 / settings.air_friction);
+
+    match settings.muscle_model {
+        MuscleModel::Legacy => {
+            let sign = (target_length - muscle_length).signum();
+            ((muscle_length - target_length) / muscle_length.max(target_length)).powf(2.0)
+                * sign
+                * strength
+        }
+        MuscleModel::SpringDamper => {
+            let spring = (target_length - muscle_length) / target_length * strength;
+            let lengthening_speed = (nodes[muscle.nodes.1].velocity
+                - nodes[muscle.nodes.0].velocity)
+                .dot(muscle_direction(muscle, nodes));
+
+            spring - lengthening_speed * muscle.damping
+        }
+    }
+}
+
+/// Length a muscle moves towards at the current time of the internal clock.
+/// Muscles triggered by contact ignore the actuation
+fn target_length(body: &Body, muscle: &BodyMuscle, actuation: MuscleActuation) -> f32 {
+    if let Trigger::GroundContact { .. } = muscle.trigger {
+        return if muscle.reflex_time > 0.0 {
+            muscle.contracted_length
+        } else {
+            muscle.extended_length
+        };
+    }
+
+    match actuation {
+        MuscleActuation::DutyCycle => {
+            if body.clocks[muscle.clock] <= muscle.contracted_time * body.clock_sizes[muscle.clock]
+            {
+                muscle.contracted_length
+            } else {
+                muscle.extended_length
+            }
+        }
+        MuscleActuation::Oscillator => {
+            let cycle = (body.clock_cycle(muscle.clock) + muscle.phase).rem_euclid(1.0);
+            let contraction = muscle.waveform.contraction(cycle, muscle.contracted_time);
+
+            muscle.contracted_length * contraction + muscle.extended_length * (1.0 - contraction)
+        }
+        MuscleActuation::NeuralNetwork | MuscleActuation::Neat => muscle.controlled_length,
+    }
+}
+
+/// Unit vector from the first node of a muscle to the second one
+fn muscle_direction(muscle: &BodyMuscle, nodes: &[BodyNode]) -> Vec2 {
+    let first_node_position = nodes[muscle.nodes.0].position;
+    let second_node_position = nodes[muscle.nodes.1].position;
+
+    if first_node_position == second_node_position {
+        return Vec2::new(0.0, 0.1);
+    }
+
+    (second_node_position - first_node_position).normalize()
+}
+
+/// Adds to every muscle the work it did while its length changed during a step.
+/// `forces` are the forces of the muscles at the start of the step.
+/// Pulling and pushing cost energy alike
+pub fn spend_energy(body: &mut Body, forces: &[f32], lengths_before: &[f32]) {
+    let lengths_after = body.muscle_lengths();
+
+    for (index, muscle) in body.muscles.iter_mut().enumerate() {
+        muscle.energy += (forces[index] * (lengths_after[index] - lengths_before[index])).abs();
+    }
+}
+
+/// Weight of every node, gravity accelerates all nodes alike whatever the time step
+fn apply_gravity(nodes: &[BodyNode], gravity: f32, forces: &mut [Vec2]) {
+    let gravity = Vec2::new(0.0, -gravity);
+
+    for (node, force) in nodes.iter().zip(forces.iter_mut()) {
+        *force += gravity * node.mass;
+    }
+}
+
+/// Slows down nodes touching the ground along its surface.
+/// Heavier nodes press harder against the ground, so the force grows with mass
+fn apply_friction(
+    nodes: &[BodyNode],
+    terrain: &Terrain,
+    obstacles: &[Obstacle],
+    forces: &mut [Vec2],
+) {
+    for (node, force) in nodes.iter().zip(forces.iter_mut()) {
+        if !touches_ground(node, terrain, obstacles) {
+            continue;
+        }
+
+        let tangent = terrain.tangent(node.position.x);
+        *force -= tangent * node.velocity.dot(tangent) * node.friction * node.mass;
+    }
+}
+
+/// Finds out if a body blew up, either numerically or by moving too fast
+pub fn check_failure(body: &Body, max_node_speed: f32) -> Option<Failure> {
+    let not_finite = body.nodes.iter().any(|node| {
+        !node.position.x.is_finite()
+            || !node.position.y.is_finite()
+            || !node.velocity.x.is_finite()
+            || !node.velocity.y.is_finite()
+    });
+
+    if not_finite {
+        Some(Failure::NotFinite)
+    } else if body
+        .nodes
+        .iter()
+        .any(|node| node.velocity.length() > max_node_speed)
+    {
+        Some(Failure::Runaway)
+    } else {
+        None
+    }
+}
+
+/// Keeps nodes from going through the ground,
+/// they lose their velocity perpendicular to it
+pub fn constrain_to_ground(body: &mut Body, terrain: &Terrain, obstacles: &[Obstacle]) {
+    for node in body.nodes.iter_mut() {
+        let ground_height = ground_height(terrain, obstacles, node.position.x);
+
+        if node.position.y < ground_height {
+            node.position.y = ground_height;
+
+            let normal = terrain.normal(node.position.x);
+            node.velocity -= normal * node.velocity.dot(normal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{Integrator, CONFIG},
+        genetic_algorithm::{
+            creature_chromosome::CreatureChromosome,
+            muscle_phenotype::{MusclePhenotype, Waveform},
+            node_phenotype::NodePhenotype,
+        },
+        simulation2d::world::integrator::integrate,
+    };
+
+    /// Two nodes in the air joined by a muscle that is always contracting
+    fn two_node_chromosome(first_mass: f32, second_mass: f32) -> CreatureChromosome {
+        CreatureChromosome {
+            internal_clock_size: 1.0,
+            nodes: vec![
+                NodePhenotype {
+                    position: Vec2::new(0.0, 1.0),
+                    friction: 0.5,
+                    mass: first_mass,
+                },
+                NodePhenotype {
+                    position: Vec2::new(0.4, 1.0),
+                    friction: 0.5,
+                    mass: second_mass,
+                },
+            ],
+            muscles: vec![MusclePhenotype {
+                contracted_time: 1.0,
+                extended_length: 0.4,
+                contracted_length: 0.1,
+                strength: 5.0,
+                damping: 0.5,
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_damped_muscle_settles_at_target_length() {
+        let mut settings = WorldSettings::new(&CONFIG.instances[0]);
+        settings.gravity = 0.0;
+        settings.muscle_model = MuscleModel::SpringDamper;
+
+        let mut chromosome = two_node_chromosome(1.0, 1.0);
+        chromosome.muscles[0].damping = 2.0;
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+
+        for _ in 0..2000 {
+            integrate(&mut body, &settings, settings.fixed_time_step);
+        }
+
+        let length = (body.nodes[1].position - body.nodes[0].position).length();
+        assert!((length - 0.1).abs() < 1e-3, "length {}", length);
+    }
+
+    #[test]
+    fn test_falling_converges_with_smaller_time_steps() {
+        let chromosome = two_node_chromosome(1.0, 2.0);
+
+        for integrator in [
+            Integrator::SemiImplicitEuler,
+            Integrator::VelocityVerlet,
+            Integrator::RungeKutta4,
+        ] {
+            let final_positions = |delta_time: f32| {
+                let mut settings = WorldSettings::new(&CONFIG.instances[0]);
+                settings.gravity = 1.0;
+                settings.integrator = integrator;
+                settings.fixed_time_step = delta_time;
+                let mut body = Body::new(&chromosome, CONFIG.node_size);
+
+                for _ in 0..(1.0 / delta_time).round() as usize {
+                    integrate(&mut body, &settings, delta_time);
+                }
+
+                body.nodes
+                    .iter()
+                    .map(|node| node.position)
+                    .collect::<Vec<Vec2>>()
+            };
+            let difference = |first: &[Vec2], second: &[Vec2]| {
+                first
+                    .iter()
+                    .zip(second.iter())
+                    .map(|(a, b)| a.distance(*b))
+                    .fold(0.0, f32::max)
+            };
+
+            let coarse = final_positions(0.01);
+            let fine = final_positions(0.005);
+            let finest = final_positions(0.0025);
+
+            // Nodes fall about half a unit, a gravity scaled by the time step would halve that
+            assert!(coarse[0].y < 0.7, "{:?} fell to {:?}", integrator, coarse);
+            assert!(difference(&coarse, &fine) < 0.01, "{:?}", integrator);
+            assert!(
+                difference(&fine, &finest) <= difference(&coarse, &fine),
+                "{:?}",
+                integrator
+            );
+        }
+    }
+
+    #[test]
+    fn test_heavier_nodes_accelerate_less() {
+        let chromosome = two_node_chromosome(1.0, 2.0);
+        let body = Body::new(&chromosome, CONFIG.node_size);
+        let mut settings = WorldSettings::new(&CONFIG.instances[0]);
+        settings.gravity = 0.0;
+
+        let accelerations = accelerations(&body, &body.nodes, &settings);
+
+        assert_eq!(accelerations[0], -accelerations[1] * 2.0);
+    }
+
+    #[test]
+    fn test_failures_are_detected() {
+        let chromosome = two_node_chromosome(1.0, 1.0);
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+        assert_eq!(check_failure(&body, 10.0), None);
+
+        body.nodes[0].velocity = Vec2::new(20.0, 0.0);
+        assert_eq!(check_failure(&body, 10.0), Some(Failure::Runaway));
+
+        body.nodes[1].position.y = f32::NAN;
+        assert_eq!(check_failure(&body, 10.0), Some(Failure::NotFinite));
+    }
+
+    #[test]
+    fn test_square_oscillator_without_phase_matches_duty_cycle() {
+        let mut chromosome = two_node_chromosome(1.0, 1.0);
+        chromosome.muscles[0].contracted_time = 0.3;
+        chromosome.muscles[0].waveform = Waveform::Square;
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+
+        for step in 0..10 {
+            body.clocks[0] = step as f32 * 0.1;
+            let muscle = &body.muscles[0];
+
+            let oscillator = target_length(&body, muscle, MuscleActuation::Oscillator);
+            let duty_cycle = target_length(&body, muscle, MuscleActuation::DutyCycle);
+            assert!((oscillator - duty_cycle).abs() < 1e-6, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn test_phase_shifts_the_oscillation() {
+        let mut chromosome = two_node_chromosome(1.0, 1.0);
+        chromosome.muscles[0].waveform = Waveform::Triangle;
+        chromosome.muscles[0].phase = 0.5;
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+
+        let extended = target_length(&body, &body.muscles[0], MuscleActuation::Oscillator);
+        body.clocks[0] = 0.25;
+        let halfway = target_length(&body, &body.muscles[0], MuscleActuation::Oscillator);
+
+        assert!((extended - 0.4).abs() < 1e-6, "extended {}", extended);
+        assert!((halfway - 0.25).abs() < 1e-6, "halfway {}", halfway);
+    }
+
+    #[test]
+    fn test_reflex_contracts_after_touchdown() {
+        let mut chromosome = two_node_chromosome(1.0, 1.0);
+        chromosome.muscles[0].contracted_time = 0.5;
+        chromosome.muscles[0].trigger = Trigger::GroundContact { node: 1 };
+        let mut body = Body::new(&chromosome, CONFIG.node_size);
+        let target =
+            |body: &Body| target_length(body, &body.muscles[0], MuscleActuation::DutyCycle);
+
+        assert!((target(&body) - 0.4).abs() < 1e-6);
+
+        trigger_reflexes(&mut body, &[0]);
+        assert!((target(&body) - 0.4).abs() < 1e-6);
+
+        trigger_reflexes(&mut body, &[1]);
+        advance_reflexes(&mut body, 0.4);
+        assert!((target(&body) - 0.1).abs() < 1e-6);
+
+        advance_reflexes(&mut body, 0.2);
+        assert!((target(&body) - 0.4).abs() < 1e-6);
+    }
+}
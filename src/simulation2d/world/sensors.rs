@@ -0,0 +1,105 @@
+use bevy::math::Vec2;
+use serde::Serialize;
+
+use crate::config::Obstacle;
+
+use super::{
+    body::{Body, BodyNode},
+    obstacle::ground_height,
+    terrain::Terrain,
+};
+
+/// Nodes closer than this to the ground touch it
+const CONTACT_DISTANCE: f32 = 0.01;
+
+/// What a node feels
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct NodeSensors {
+    pub ground_contact: bool,
+    /// Distance to the ground right below the node
+    pub height: f32,
+    pub velocity: Vec2,
+}
+
+/// What a muscle feels
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct MuscleSensors {
+    pub length: f32,
+}
+
+/// Readings of every sensor of a body, in the same order as its nodes and muscles
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Sensors {
+    pub nodes: Vec<NodeSensors>,
+    pub muscles: Vec<MuscleSensors>,
+}
+
+/// Reads the sensors of a body in its current state
+pub fn read_sensors(body: &Body, terrain: &Terrain, obstacles: &[Obstacle]) -> Sensors {
+    Sensors {
+        nodes: body
+            .nodes
+            .iter()
+            .map(|node| {
+                let height = node.position.y - ground_height(terrain, obstacles, node.position.x);
+
+                NodeSensors {
+                    ground_contact: height <= CONTACT_DISTANCE,
+                    height,
+                    velocity: node.velocity,
+                }
+            })
+            .collect(),
+        muscles: body
+            .muscle_lengths()
+            .into_iter()
+            .map(|length| MuscleSensors { length })
+            .collect(),
+    }
+}
+
+/// Nodes that started touching the ground between two readings
+pub fn touchdowns(before: &Sensors, after: &Sensors) -> Vec<usize> {
+    before
+        .nodes
+        .iter()
+        .zip(after.nodes.iter())
+        .enumerate()
+        .filter(|(_, (before, after))| !before.ground_contact && after.ground_contact)
+        .map(|(node, _)| node)
+        .collect()
+}
+
+/// Whether a node touches the ground, friction only acts on these nodes
+pub fn touches_ground(node: &BodyNode, terrain: &Terrain, obstacles: &[Obstacle]) -> bool {
+    node.position.y - ground_height(terrain, obstacles, node.position.x) <= CONTACT_DISTANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::TerrainProfile, simulation2d::world::body::BodyMuscle};
+
+    #[test]
+    fn test_sensors_read_contact_and_length() {
+        let node = |x: f32, y: f32| BodyNode {
+            position: Vec2::new(x, y),
+            ..Default::default()
+        };
+        let body = Body {
+            nodes: vec![node(0.0, 0.0), node(0.0, 0.3)],
+            muscles: vec![BodyMuscle {
+                nodes: (0, 1),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let sensors = read_sensors(&body, &Terrain::new(&TerrainProfile::Flat), &[]);
+
+        assert!(sensors.nodes[0].ground_contact);
+        assert!(!sensors.nodes[1].ground_contact);
+        assert!((sensors.nodes[1].height - 0.3).abs() < 1e-6);
+        assert!((sensors.muscles[0].length - 0.3).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,179 @@
+use std::{fs::File, sync::Arc};
+
+use bevy::{log::warn, math::Vec2};
+use ron::de::from_reader;
+
+use crate::config::TerrainProfile;
+
+/// Ground of the world, its height is a function of x
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terrain {
+    Flat,
+    Slope {
+        slope: f32,
+    },
+    SineHills {
+        amplitude: f32,
+        wavelength: f32,
+    },
+    Steps {
+        height: f32,
+        width: f32,
+    },
+    /// Heights sampled every `spacing` starting at `start`, linearly interpolated.
+    /// Beyond the samples the terrain stays at the height of the closest one
+    Heightmap {
+        start: f32,
+        spacing: f32,
+        heights: Arc<Vec<f32>>,
+    },
+}
+
+impl Terrain {
+    /// Creates the terrain described by a profile, loading its heightmap if it has one
+    pub fn new(profile: &TerrainProfile) -> Terrain {
+        match profile {
+            TerrainProfile::Flat => Terrain::Flat,
+            TerrainProfile::Slope { slope } => Terrain::Slope { slope: *slope },
+            TerrainProfile::SineHills {
+                amplitude,
+                wavelength,
+            } => Terrain::SineHills {
+                amplitude: *amplitude,
+                wavelength: *wavelength,
+            },
+            TerrainProfile::Steps { height, width } => Terrain::Steps {
+                height: *height,
+                width: *width,
+            },
+            TerrainProfile::Heightmap {
+                path,
+                start,
+                spacing,
+            } => match read_heights(path) {
+                Ok(heights) if !heights.is_empty() => Terrain::Heightmap {
+                    start: *start,
+                    spacing: *spacing,
+                    heights: Arc::new(heights),
+                },
+                Ok(_) => {
+                    warn!("Heightmap {} has no heights. Using flat terrain.", path);
+                    Terrain::Flat
+                }
+                Err(error) => {
+                    warn!("Heightmap file error. {}. Using flat terrain.", error.code);
+                    Terrain::Flat
+                }
+            },
+        }
+    }
+
+    pub fn height(&self, x: f32) -> f32 {
+        match self {
+            Terrain::Flat => 0.0,
+            Terrain::Slope { slope } => slope * x,
+            Terrain::SineHills {
+                amplitude,
+                wavelength,
+            } => amplitude * (x * std::f32::consts::TAU / wavelength).sin(),
+            Terrain::Steps { height, width } => (x / width).floor() * height,
+            Terrain::Heightmap {
+                start,
+                spacing,
+                heights,
+            } => {
+                let position = (x - start) / spacing;
+
+                if position <= 0.0 {
+                    return heights[0];
+                }
+
+                let index = position.floor() as usize;
+                if index + 1 >= heights.len() {
+                    return heights[heights.len() - 1];
+                }
+
+                let fraction = position - index as f32;
+                heights[index] + (heights[index + 1] - heights[index]) * fraction
+            }
+        }
+    }
+
+    /// Derivative of the height. Vertical faces of steps are walls,
+    /// resolved with the obstacles
+    pub fn slope(&self, x: f32) -> f32 {
+        match self {
+            Terrain::Flat | Terrain::Steps { .. } => 0.0,
+            Terrain::Slope { slope } => *slope,
+            Terrain::SineHills {
+                amplitude,
+                wavelength,
+            } => {
+                let frequency = std::f32::consts::TAU / wavelength;
+                amplitude * frequency * (x * frequency).cos()
+            }
+            Terrain::Heightmap {
+                start,
+                spacing,
+                heights,
+            } => {
+                let position = (x - start) / spacing;
+                let index = position.floor();
+
+                if index < 0.0 || index as usize + 1 >= heights.len() {
+                    return 0.0;
+                }
+
+                let index = index as usize;
+                (heights[index + 1] - heights[index]) / spacing
+            }
+        }
+    }
+
+    /// Unit vector perpendicular to the ground, pointing up
+    pub fn normal(&self, x: f32) -> Vec2 {
+        Vec2::new(-self.slope(x), 1.0).normalize()
+    }
+
+    /// Unit vector along the ground, pointing right
+    pub fn tangent(&self, x: f32) -> Vec2 {
+        Vec2::new(1.0, self.slope(x)).normalize()
+    }
+}
+
+fn read_heights(path: &str) -> Result<Vec<f32>, ron::error::Error> {
+    let file = File::open(path)?;
+    let heights: Vec<f32> = from_reader(file)?;
+    Ok(heights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_heightmap_interpolates_and_clamps() {
+        let terrain = Terrain::Heightmap {
+            start: -1.0,
+            spacing: 0.5,
+            heights: Arc::new(vec![0.0, 1.0, 0.5]),
+        };
+
+        assert_close(terrain.height(-2.0), 0.0);
+        assert_close(terrain.height(-0.75), 0.5);
+        assert_close(terrain.height(-0.25), 0.75);
+        assert_close(terrain.height(3.0), 0.5);
+        assert_close(terrain.slope(-0.75), 2.0);
+        assert_close(terrain.slope(3.0), 0.0);
+    }
+
+    #[test]
+    fn test_flat_normal_and_tangent() {
+        assert_eq!(Terrain::Flat.normal(1.0), Vec2::new(0.0, 1.0));
+        assert_eq!(Terrain::Flat.tangent(1.0), Vec2::new(1.0, 0.0));
+    }
+}